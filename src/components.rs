@@ -1,4 +1,8 @@
 use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::resources::{Action, ControlMode, DisplayMode, Settings};
+use crate::utils::{DASH_COOLDOWN_SECONDS, DASH_SECONDS};
 
 #[derive(Component)]
 pub struct Player {
@@ -6,11 +10,101 @@ pub struct Player {
     pub color_index: usize,
 }
 
+#[derive(Component)]
+pub struct Lives {
+    pub value: u32,
+}
+
+/// Brief invulnerability after losing a life or a shield. The sprite blinks
+/// until the timer runs out.
+#[derive(Component)]
+pub struct Recovering {
+    pub timer: Timer,
+}
+
+/// The player's dash: a burst along `velocity` while `timer` runs, with the
+/// next one ready once `cooldown` has run out.
+#[derive(Component)]
+pub struct Dash {
+    pub velocity: Vec3,
+    pub timer: Timer,
+    pub cooldown: Timer,
+}
+impl Default for Dash {
+    fn default() -> Dash {
+        let mut timer = Timer::from_seconds(DASH_SECONDS, TimerMode::Once);
+        timer.tick(timer.duration());
+        let mut cooldown = Timer::from_seconds(DASH_COOLDOWN_SECONDS, TimerMode::Once);
+        cooldown.tick(cooldown.duration());
+        Dash {
+            velocity: Vec3::ZERO,
+            timer,
+            cooldown,
+        }
+    }
+}
+impl Dash {
+    pub fn is_dashing(&self) -> bool {
+        !self.timer.finished()
+    }
+}
+
+/// Shields around the player, each good for one hit.
+#[derive(Component, Default)]
+pub struct Shields {
+    pub count: u32,
+}
+
+/// Push away from the agent that just hit the player, decaying every tick.
+#[derive(Component)]
+pub struct Knockback {
+    pub velocity: Vec3,
+}
+
+/// An agent that has come within `NEAR_MISS_DISTANCE` of the player. It
+/// counts as a near miss if it leaves again without landing a hit.
+#[derive(Component)]
+pub struct NearMiss {}
+
 #[derive(Component)]
 pub struct Enemy {
     pub direction: Vec2,
 }
 
+/// Which kind of agent an `Enemy` is. Waves pick between them using the
+/// `mix` in their `WaveDefinition`.
+#[derive(Component, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    /// Moves in a straight line, bouncing off the edges of the arena.
+    Bouncer,
+    /// Steers toward the player, turning and moving at a capped rate.
+    Chaser,
+}
+impl EnemyKind {
+    pub fn tint(&self) -> Color {
+        match self {
+            EnemyKind::Bouncer => Color::WHITE,
+            EnemyKind::Chaser => Color::rgb(1.0, 0.45, 0.45),
+        }
+    }
+}
+
+/// Where a simulated entity was at the last two gameplay ticks. `Transform`
+/// is blended between them for rendering.
+#[derive(Component)]
+pub struct Interpolated {
+    pub previous: Vec3,
+    pub current: Vec3,
+}
+impl Interpolated {
+    pub fn new(translation: Vec3) -> Interpolated {
+        Interpolated {
+            previous: translation,
+            current: translation,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Star {}
 
@@ -20,6 +114,43 @@ pub struct FPS {}
 #[derive(Component)]
 pub struct ScoreComponent {}
 
+#[derive(Component)]
+pub struct LivesDisplay {}
+
+#[derive(Component)]
+pub struct ComboMeter {}
+
+#[derive(Component)]
+pub struct PowerUpTimerDisplay {}
+
+#[derive(Component)]
+pub struct ShieldDisplay {}
+
+/// The ring flying off a shield as it breaks.
+#[derive(Component)]
+pub struct ShieldBurst {
+    pub timer: Timer,
+}
+
+/// The grey wash over the arena while time is slowed.
+#[derive(Component)]
+pub struct TimeSlowTint {}
+
+/// The points from a pickup, drifting up from where it was collected.
+#[derive(Component)]
+pub struct ScorePopup {
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+pub struct WaveBanner {}
+
+/// A short notice at the top of the screen, gone when `timer` runs out.
+#[derive(Component)]
+pub struct Toast {
+    pub timer: Timer,
+}
+
 #[derive(Component)]
 pub enum MenuButtonAction {
     Play,
@@ -36,7 +167,183 @@ pub struct PlayButton {}
 pub struct QuitButton {}
 
 #[derive(Component)]
-pub struct Invinci {}
+pub struct HighScoresButton {}
+
+/// Returns from a menu page to the main menu.
+#[derive(Component)]
+pub struct BackButton {}
+
+#[derive(Component)]
+pub struct HighScoresMenu {}
+
+#[derive(Component)]
+pub struct OptionsButton {}
+
+#[derive(Component)]
+pub struct StatsButton {}
+
+#[derive(Component)]
+pub struct StatsMenu {}
+
+#[derive(Component)]
+pub struct AchievementsButton {}
+
+#[derive(Component)]
+pub struct AchievementsMenu {}
+
+#[derive(Component)]
+pub struct OptionsMenu {}
+
+#[derive(Component)]
+pub struct GameOverMenu {}
+
+/// Starts a new run straight from the game-over screen.
+#[derive(Component)]
+pub struct RetryButton {}
+
+/// Leaves the game-over screen for the main menu.
+#[derive(Component)]
+pub struct MainMenuButton {}
+
+#[derive(Component)]
+pub struct SaveReplayButton {}
+
+/// One adjustable row of the Options page.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionKind {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    ShowFps,
+    ScreenShake,
+    ControlMode,
+    /// The keys bound to an action.
+    Binding(Action),
+}
+impl OptionKind {
+    /// Every row, top to bottom.
+    pub fn all() -> Vec<OptionKind> {
+        let mut options = vec![
+            OptionKind::MasterVolume,
+            OptionKind::MusicVolume,
+            OptionKind::SfxVolume,
+            OptionKind::Fullscreen,
+            OptionKind::ShowFps,
+            OptionKind::ScreenShake,
+            OptionKind::ControlMode,
+        ];
+        options.extend(Action::ALL.map(OptionKind::Binding));
+        options
+    }
+
+    pub fn is_toggle(&self) -> bool {
+        matches!(
+            self,
+            OptionKind::Fullscreen
+                | OptionKind::ShowFps
+                | OptionKind::ScreenShake
+                | OptionKind::ControlMode
+        )
+    }
+
+    pub fn label(&self, settings: &Settings) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self {
+            OptionKind::MasterVolume => {
+                format!("Master Volume: {:.0}%", settings.master_volume * 100.0)
+            }
+            OptionKind::MusicVolume => {
+                format!("Music Volume: {:.0}%", settings.music_volume * 100.0)
+            }
+            OptionKind::SfxVolume => format!("SFX Volume: {:.0}%", settings.sfx_volume * 100.0),
+            OptionKind::Fullscreen => format!(
+                "Fullscreen: {}",
+                on_off(settings.display_mode != DisplayMode::Windowed)
+            ),
+            OptionKind::ShowFps => format!("Show FPS: {}", on_off(settings.show_fps)),
+            OptionKind::ScreenShake => format!("Screen Shake: {}", on_off(settings.screen_shake)),
+            OptionKind::ControlMode => match settings.control_mode {
+                ControlMode::Keyboard => "Controls: Keyboard / Pad".to_string(),
+                ControlMode::Mouse => "Controls: Follow Mouse".to_string(),
+            },
+            OptionKind::Binding(action) => {
+                let keys: Vec<String> = settings
+                    .bindings
+                    .keys(*action)
+                    .iter()
+                    .map(|key| format!("{:?}", key))
+                    .collect();
+                format!("{}: {}", action.label(), keys.join(" / "))
+            }
+        }
+    }
+
+    /// Nudges a volume by one step in the direction of `step`, or flips a
+    /// toggle whichever way `step` points. Bindings change through
+    /// `RebindCapture` instead.
+    pub fn adjust(&self, settings: &mut Settings, step: i8) {
+        let nudge = |volume: &mut f32| {
+            *volume = (((*volume * 10.0).round() + step as f32) / 10.0).clamp(0.0, 1.0);
+        };
+        match self {
+            OptionKind::MasterVolume => nudge(&mut settings.master_volume),
+            OptionKind::MusicVolume => nudge(&mut settings.music_volume),
+            OptionKind::SfxVolume => nudge(&mut settings.sfx_volume),
+            OptionKind::Fullscreen => {
+                settings.display_mode = if settings.display_mode == DisplayMode::Windowed {
+                    DisplayMode::Fullscreen
+                } else {
+                    DisplayMode::Windowed
+                }
+            }
+            OptionKind::ShowFps => settings.show_fps = !settings.show_fps,
+            OptionKind::ScreenShake => settings.screen_shake = !settings.screen_shake,
+            OptionKind::ControlMode => {
+                settings.control_mode = match settings.control_mode {
+                    ControlMode::Keyboard => ControlMode::Mouse,
+                    ControlMode::Mouse => ControlMode::Keyboard,
+                }
+            }
+            OptionKind::Binding(_action) => {}
+        }
+    }
+}
+
+/// The text of an Options row, refreshed as the setting changes.
+#[derive(Component)]
+pub struct OptionText {
+    pub option: OptionKind,
+}
+
+/// A -, + or toggle button on an Options row.
+#[derive(Component)]
+pub struct OptionButton {
+    pub option: OptionKind,
+    pub step: i8,
+}
+
+/// Waits for the next key press and binds it to `action`.
+#[derive(Component)]
+pub struct RebindButton {
+    pub action: Action,
+}
+
+#[derive(Component)]
+pub struct ResetControlsButton {}
+
+#[derive(Component)]
+pub struct NameEntryMenu {}
+
+#[derive(Component)]
+pub struct NameEntryText {}
+
+/// A power-up waiting on the field to be collected.
+#[derive(Component)]
+pub struct PowerUpPickup {
+    /// The `PowerUp` definition it was spawned from.
+    pub name: String,
+}
 
 #[derive(Component)]
 pub struct DrawEnemyNumber {}
@@ -47,10 +354,11 @@ pub struct SoundButton {}
 #[derive(Component)]
 pub struct MrProducerSong {}
 
+/// Music started by a power-up, paused along with the game.
 #[derive(Component)]
-pub struct InvinciSong {}
+pub struct PowerUpSong {}
 
+/// Any playing track that follows the music volume rather than the sound
+/// effects volume.
 #[derive(Component)]
-pub struct Coffee {
-    pub collected: bool,
-}
+pub struct Music {}
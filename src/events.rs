@@ -1,9 +1,58 @@
+use std::collections::BTreeMap;
+
 use bevy::prelude::*;
 
-#[derive(Event)]
+use crate::resources::PowerUpEffect;
+
+/// The last life is gone. Carries the summary shown on the game-over screen.
+#[derive(Event, Clone, Debug, PartialEq)]
 pub struct GameOver {
     pub score: u32,
+    /// The wave the run ended in, counting from 1.
+    pub wave: u32,
+    pub survival_secs: f32,
+    /// Agents on screen when the last life went.
+    pub agents: u32,
+    pub money: u32,
+    /// Power-ups collected, by name.
+    pub power_ups: BTreeMap<String, u32>,
+    /// The seed the run was played with, so it can be shared or replayed.
+    pub seed: u64,
 }
 
 #[derive(Event)]
 pub struct GameStart {}
+
+/// An agent got the player. Sent for the final hit too, before `GameOver`.
+#[derive(Event)]
+pub struct PlayerHit {
+    pub lives: u32,
+}
+
+/// Money picked up at `position`, worth `points` at the combo `multiplier`.
+#[derive(Event)]
+pub struct StarCollected {
+    pub position: Vec2,
+    pub points: u32,
+    pub multiplier: u32,
+}
+
+#[derive(Event)]
+pub struct PowerUpCollected {
+    pub name: String,
+    pub effect: PowerUpEffect,
+}
+
+/// A shield took a hit from the agent at `position`, which is gone.
+#[derive(Event)]
+pub struct ShieldAbsorbed {
+    pub position: Vec2,
+    /// Shields still up.
+    pub shields: u32,
+}
+
+/// A wave of agents begins, counting from 1.
+#[derive(Event)]
+pub struct WaveStarted {
+    pub number: u32,
+}
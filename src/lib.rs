@@ -0,0 +1,9 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+pub mod components;
+pub mod events;
+pub mod plugins;
+pub mod resources;
+pub mod styles;
+pub mod systems;
+pub mod utils;
@@ -1,88 +1,56 @@
-pub mod components;
-pub mod events;
-pub mod resources;
-pub mod styles;
-pub mod systems;
-pub mod utils;
+use top_g_simulator::{plugins::*, resources::*, systems::*, utils::*};
 
-use events::*;
-use resources::*;
-use systems::*;
-
-use bevy::{
-    prelude::*,
-    window::{PresentMode, WindowMode},
-};
+use bevy::{log::LogPlugin, prelude::*};
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
+    let launch_options = LaunchOptions::from_args();
+    let replay = launch_options.replay.as_ref().map(|path| {
+        Replay::load(path).unwrap_or_else(|error| {
+            eprintln!("Could not load replay {}: {}", path.display(), error);
+            std::process::exit(1);
+        })
+    });
+
+    let mut app = App::new();
+    app.insert_resource(GameRng::new(
+        replay
+            .as_ref()
+            .map(|replay| replay.seed)
+            .or(launch_options.seed),
+    ));
+    if launch_options.headless || replay.is_some() {
+        app.add_systems(Startup, start_game);
+    }
+    if let Some(replay) = replay {
+        app.insert_resource(ReplayPlayback { replay, cursor: 0 });
+    }
+
+    if launch_options.headless {
+        // Sprites and sounds have no loaders without the renderer and audio.
+        app.add_plugins(LogPlugin {
+            filter: "bevy_asset=error".into(),
+            ..default()
+        })
+        .add_plugins(HeadlessPlugin)
+        .add_systems(Update, exit_headless_game);
+    } else {
+        let (settings, settings_error) = load_settings();
+        let (width, height) = settings.resolution;
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Top G Simulator".into(),
-                resolution: (1920., 1080.).into(),
-                mode: WindowMode::Fullscreen,
-                present_mode: PresentMode::AutoVsync,
+                resolution: (width as f32, height as f32).into(),
+                mode: window_mode(settings.display_mode),
+                present_mode: present_mode(settings.vsync),
                 ..default()
             }),
             ..default()
-        }))
-        .init_resource::<Score>()
-        .init_resource::<SpawnEnemyTimer>()
-        .init_resource::<Enemies>()
-        .init_resource::<FpsTracker>()
-        .init_resource::<FirstGame>()
-        .init_resource::<SpawnInvinciTimer>()
-        .init_resource::<InvinciDurationTimer>()
-        .init_resource::<FixMenuTimer>()
-        .init_resource::<MrProducerTimer>()
-        .init_resource::<SpawnCoffeeTimer>()
-        .add_state::<GameState>()
-        .add_state::<Invincible>()
-        .add_state::<MrProducerState>()
-        .add_event::<GameStart>()
-        .add_event::<GameOver>()
-        .add_systems(Startup, (spawn_camera, setup_cursor))
-        .add_systems(OnEnter(GameState::Menu), (spawn_main_menu, toggle_cursor))
-        .add_systems(OnExit(GameState::Menu), (spawn_player, toggle_cursor))
-        .add_systems(OnEnter(GameState::Paused), (spawn_main_menu, toggle_cursor))
-        .add_systems(OnExit(GameState::Paused), toggle_cursor)
-        .add_systems(
-            Update,
-            (
-                game_start_event.before(spawn_enemies),
-                spawn_enemies,
-                interact_with_play_button.run_if(not(in_state(GameState::Game))),
-                interact_with_quit_button.run_if(not(in_state(GameState::Game))),
-                window_border_movement.run_if(in_state(GameState::Game)),
-                player_movement.run_if(in_state(GameState::Game)),
-                enemy_movement.run_if(not(in_state(GameState::Paused))),
-                confine_enemy_to_window,
-                detect_collision.run_if(in_state(GameState::Game)),
-                spawn_stars,
-                collect_stars,
-                update_score,
-                pause_game.run_if(not(in_state(GameState::Menu))),
-                fps_system,
-                game_over_event_receiver,
-                despawn_main_menu.run_if(in_state(GameState::Game)),
-                update_player_colors.run_if(in_state(GameState::Game)),
-                spawn_invincibility.run_if(in_state(GameState::Game)),
-                collect_invincibility.run_if(in_state(GameState::Game)),
-                draw_enemy_number,
-            ),
-        )
-        .add_systems(
-            Update,
-            (
-                tick_enemy_timer.run_if(in_state(GameState::Game)),
-                fix_menu_first_game.run_if(in_state(GameState::Menu)),
-                interact_with_sound_button.run_if(not(in_state(GameState::Game))),
-                spawn_coffee.run_if(in_state(GameState::Game)),
-                collect_coffee
-                    .run_if(in_state(GameState::Game))
-                    .before(spawn_stars),
-                mr_producer,
-            ),
-        )
-        .run();
+        }));
+        if let Some(error) = settings_error {
+            warn!("{}, using the default settings", error);
+        }
+        app.insert_resource(settings).add_plugins(TopGPlugin);
+    }
+
+    app.insert_resource(launch_options).run();
 }
@@ -0,0 +1,667 @@
+use std::time::Duration;
+
+use bevy::{
+    app::PluginGroupBuilder,
+    asset::FileAssetIo,
+    input::{InputPlugin, InputSystem},
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+
+use crate::events::*;
+use crate::resources::*;
+use crate::systems::*;
+use crate::utils::*;
+
+/// Ordering of the gameplay systems registered by the plugins below. `Start`
+/// runs in `Update`, reacting to `GameStart` as soon as it is sent; the rest
+/// run once per tick in `FixedUpdate`.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    /// Resetting the world when a `GameStart` arrives.
+    Start,
+    /// Filling `PlayerInput`, live or from a replay.
+    Input,
+    Movement,
+    /// Pickups and enemy contact, once everything has moved.
+    Collect,
+    /// Everything that draws from `GameRng`, in a fixed order.
+    Spawn,
+}
+
+/// The whole game. Individual plugins can be swapped out or disabled, e.g.
+/// `TopGPlugin.build().disable::<AudioPlugin>()`.
+pub struct TopGPlugin;
+
+impl PluginGroup for TopGPlugin {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(CorePlugin)
+            .add(ActionPlugin)
+            .add(PlayerPlugin)
+            .add(WavePlugin)
+            .add(EnemyPlugin)
+            .add(PickupPlugin)
+            .add(MenuPlugin)
+            .add(HudPlugin)
+            .add(AudioPlugin)
+            .add(ReplayPlugin)
+            .add(HighScorePlugin)
+            .add(SettingsPlugin)
+            .add(OptionsPlugin)
+            .add(GameOverPlugin)
+            .add(StatsPlugin)
+            .add(AchievementPlugin)
+    }
+}
+
+/// The gameplay plugins on `MinimalPlugins`, without a window, renderer,
+/// menus or audio. Every `App::update` advances the game by exactly one tick,
+/// so it runs as fast as the CPU allows.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), InputPlugin))
+            .add_plugins(
+                TopGPlugin
+                    .build()
+                    .disable::<MenuPlugin>()
+                    .disable::<HudPlugin>()
+                    .disable::<AudioPlugin>()
+                    .disable::<HighScorePlugin>()
+                    .disable::<SettingsPlugin>()
+                    .disable::<OptionsPlugin>()
+                    .disable::<GameOverPlugin>()
+                    .disable::<StatsPlugin>()
+                    .disable::<AchievementPlugin>(),
+            )
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                TIME_STEP,
+            )));
+    }
+}
+
+/// State, events and resources shared by every other plugin, plus the fixed
+/// gameplay tick and the interpolation that smooths it for rendering.
+pub struct CorePlugin;
+
+impl Plugin for CorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LaunchOptions>()
+            .init_resource::<Arena>()
+            .init_resource::<GameRng>()
+            .init_resource::<FirstGame>()
+            .init_resource::<Score>()
+            .init_resource::<Enemies>()
+            .init_resource::<SurvivalTime>()
+            .init_resource::<RunStats>()
+            .insert_resource(FixedTime::new_from_secs(TIME_STEP))
+            .add_state::<GameState>()
+            .add_event::<GameStart>()
+            .add_event::<GameOver>()
+            .add_event::<PlayerHit>()
+            .add_event::<StarCollected>()
+            .add_event::<PowerUpCollected>()
+            .add_event::<ShieldAbsorbed>()
+            .add_event::<WaveStarted>()
+            .configure_sets(
+                FixedUpdate,
+                (
+                    GameplaySet::Input,
+                    GameplaySet::Movement,
+                    GameplaySet::Collect,
+                    GameplaySet::Spawn,
+                )
+                    .chain(),
+            )
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                PreUpdate,
+                update_arena.run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    restore_simulated_transforms.before(GameplaySet::Input),
+                    store_simulated_transforms.after(GameplaySet::Spawn),
+                    tick_survival_time.run_if(in_state(GameState::Game)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    game_start_event.in_set(GameplaySet::Start),
+                    game_over_event_receiver,
+                    interpolate_transforms,
+                ),
+            );
+    }
+}
+
+/// Maps keys to `Action`s through `Bindings`, and gamepad buttons to their
+/// fixed actions, filling `Input<Action>` before anything else runs each
+/// frame. Gamepads are looked up every frame, so they can come and go at
+/// any time.
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bindings>()
+            .init_resource::<Input<Action>>()
+            .add_systems(PreUpdate, update_actions.after(InputSystem));
+    }
+}
+
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerInput>()
+            .init_resource::<StartingLives>()
+            .init_resource::<ControlMode>()
+            .init_resource::<MouseTarget>()
+            .init_resource::<DashRequested>()
+            .add_systems(OnExit(GameState::Menu), spawn_player)
+            .add_systems(
+                OnTransition {
+                    from: GameState::GameOver,
+                    to: GameState::Game,
+                },
+                spawn_player,
+            )
+            // Before the fixed loop, so a press is seen by this frame's ticks.
+            .add_systems(PreUpdate, request_dash.after(update_actions))
+            .add_systems(
+                FixedUpdate,
+                (
+                    read_player_input
+                        .run_if(not(resource_exists::<ReplayPlayback>()))
+                        .in_set(GameplaySet::Input),
+                    (
+                        update_dash,
+                        player_movement,
+                        apply_knockback,
+                        window_border_movement,
+                        track_distance,
+                        tick_recovery,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Game))
+                        .in_set(GameplaySet::Movement),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_mouse_target,
+                    update_player_colors.run_if(in_state(GameState::Game)),
+                ),
+            );
+    }
+}
+
+/// Loads the difficulty curve and moves the run through its waves. Tests can
+/// insert their own `WaveCurve` before adding the plugin.
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<WaveCurve>() {
+            let path = FileAssetIo::get_base_path().join(WaveCurve::PATH);
+            let wave_curve = WaveCurve::load(&path).unwrap_or_else(|error| {
+                warn!("Could not load {}: {}", path.display(), error);
+                WaveCurve::default()
+            });
+            app.insert_resource(wave_curve);
+        }
+
+        app.init_resource::<Wave>()
+            .add_systems(
+                Update,
+                reset_waves
+                    .in_set(GameplaySet::Start)
+                    .after(game_start_event),
+            )
+            .add_systems(
+                FixedUpdate,
+                advance_wave
+                    .run_if(in_state(GameState::Game))
+                    .in_set(GameplaySet::Spawn)
+                    .before(spawn_enemies),
+            );
+    }
+}
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnEnemyTimer>()
+            .init_resource::<EnemyTimeScale>()
+            .add_systems(
+                Update,
+                spawn_initial_enemies
+                    .in_set(GameplaySet::Start)
+                    .after(reset_waves),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    update_enemy_time_scale.in_set(GameplaySet::Input),
+                    (
+                        enemy_movement.run_if(not(in_state(GameState::Paused))),
+                        collide_enemies,
+                        confine_enemy_to_window,
+                    )
+                        .chain()
+                        .in_set(GameplaySet::Movement),
+                    // Agents broken on a shield are gone before near misses
+                    // are counted.
+                    (detect_collision, apply_deferred, count_near_misses)
+                        .chain()
+                        .run_if(in_state(GameState::Game))
+                        .in_set(GameplaySet::Collect),
+                    (tick_enemy_timer, spawn_enemies)
+                        .chain()
+                        .run_if(in_state(GameState::Game))
+                        .in_set(GameplaySet::Spawn),
+                ),
+            );
+    }
+}
+
+/// Money and the power-ups from `power_ups.ron`.
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PowerUps>() {
+            let path = FileAssetIo::get_base_path().join(PowerUps::PATH);
+            let power_ups = load_ron(&path).unwrap_or_else(|error| {
+                warn!("Could not load {}: {}", path.display(), error);
+                PowerUps::default()
+            });
+            app.insert_resource(power_ups);
+        }
+
+        app.init_resource::<PowerUpTimers>()
+            .init_resource::<ActivePowerUps>()
+            .init_resource::<Combo>()
+            .add_systems(
+                Update,
+                reset_pickup_timers
+                    .in_set(GameplaySet::Start)
+                    .after(spawn_initial_enemies),
+            )
+            .add_systems(
+                FixedUpdate,
+                attract_money
+                    .run_if(in_state(GameState::Game))
+                    .in_set(GameplaySet::Movement)
+                    .after(window_border_movement),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (tick_combo.run_if(in_state(GameState::Game)), collect_stars).chain(),
+                    (tick_power_ups, collect_power_ups, grant_shields)
+                        .chain()
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .in_set(GameplaySet::Collect),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    spawn_stars.run_if(not(in_state(GameState::Paused))),
+                    spawn_power_ups.run_if(in_state(GameState::Game)),
+                )
+                    .chain()
+                    .in_set(GameplaySet::Spawn)
+                    .after(spawn_enemies),
+            );
+    }
+}
+
+/// Main and pause menus, plus the cursor handling that goes with them.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FixMenuTimer>()
+            .init_resource::<MenuFocus>()
+            .add_state::<MenuScreen>()
+            .add_systems(Startup, setup_cursor)
+            .add_systems(OnEnter(GameState::Menu), (spawn_main_menu, toggle_cursor))
+            .add_systems(OnExit(GameState::Menu), toggle_cursor)
+            .add_systems(OnEnter(GameState::Paused), (spawn_main_menu, toggle_cursor))
+            .add_systems(OnExit(GameState::Paused), toggle_cursor)
+            .add_systems(
+                Update,
+                (
+                    (
+                        interact_with_play_button.run_if(not(in_state(GameState::Game))),
+                        interact_with_quit_button.run_if(not(in_state(GameState::Game))),
+                    )
+                        .before(GameplaySet::Start),
+                    pause_game
+                        .run_if(not(in_state(GameState::Menu)))
+                        .run_if(in_state(MenuScreen::Main)),
+                    interact_with_back_button,
+                    back_to_main_menu,
+                    navigate_menu_buttons
+                        .after(back_to_main_menu)
+                        .run_if(not(in_state(GameState::Game)))
+                        .run_if(not(in_state(MenuScreen::Options))),
+                    despawn_main_menu.run_if(in_state(GameState::Game)),
+                    fix_menu_first_game.run_if(in_state(GameState::Menu)),
+                ),
+            );
+    }
+}
+
+/// Score, lives, shields, agent count, wave banner and FPS readouts; the
+/// combo meter and its score popups; timers for running power-ups; the
+/// time-slow tint, dash cooldown ring and shield bubbles; and screen shake.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FpsTracker>()
+            .init_resource::<ScreenShake>()
+            .add_systems(
+                Update,
+                (
+                    update_score,
+                    draw_lives,
+                    draw_shields,
+                    draw_combo_meter,
+                    draw_power_up_timers,
+                    draw_time_slow_tint.run_if(resource_changed::<EnemyTimeScale>()),
+                    tint_new_agents,
+                    draw_dash_cooldown.run_if(in_state(GameState::Game)),
+                    draw_shield_bubbles.run_if(in_state(GameState::Game)),
+                    (spawn_shield_bursts, animate_shield_bursts),
+                    spawn_score_popups,
+                    animate_score_popups,
+                    draw_enemy_number,
+                    draw_wave_banner,
+                    fps_system,
+                    (start_screen_shake, shake_camera).chain(),
+                ),
+            );
+    }
+}
+
+/// Background music, pickup jingles and sound effects.
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MrProducerTimer>()
+            .add_state::<MrProducerState>()
+            .add_systems(OnEnter(GameState::Paused), pause_power_up_music)
+            .add_systems(OnExit(GameState::Paused), resume_power_up_music)
+            .add_systems(
+                Update,
+                (
+                    interact_with_sound_button.run_if(not(in_state(GameState::Game))),
+                    sync_music_setting.run_if(resource_changed::<Settings>()),
+                    apply_volume_settings.run_if(resource_changed::<Settings>()),
+                    mr_producer,
+                    play_star_sound,
+                    play_power_up_sounds,
+                    play_hit_sound,
+                    play_shield_sound,
+                    slow_music,
+                ),
+            );
+    }
+}
+
+/// The local leaderboard: name entry after a qualifying run and the High
+/// Scores menu page. Loads and saves `high_scores.ron` in the data directory.
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        let high_scores: HighScores = load_or_default(HighScores::FILE);
+
+        app.insert_resource(high_scores)
+            .add_systems(
+                OnEnter(MenuScreen::HighScores),
+                (despawn_main_menu, spawn_high_scores_menu),
+            )
+            .add_systems(OnExit(MenuScreen::HighScores), despawn_high_scores_menu)
+            .add_systems(
+                OnTransition {
+                    from: MenuScreen::HighScores,
+                    to: MenuScreen::Main,
+                },
+                spawn_main_menu,
+            )
+            .add_systems(OnEnter(MenuScreen::NameEntry), spawn_name_entry_menu)
+            .add_systems(OnExit(MenuScreen::NameEntry), despawn_name_entry_menu)
+            .add_systems(
+                Update,
+                (
+                    queue_high_score,
+                    interact_with_high_scores_button,
+                    type_high_score_name,
+                ),
+            );
+    }
+}
+
+/// Keeps `Settings` applied to the window and saved to disk. `main` loads
+/// the file before the window opens; otherwise it is loaded here.
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<Settings>() {
+            let (settings, error) = load_settings();
+            if let Some(error) = error {
+                warn!("{}, using the default settings", error);
+            }
+            app.insert_resource(settings);
+        }
+
+        app.add_systems(
+            Update,
+            (apply_window_settings, apply_input_settings, save_settings)
+                .run_if(resource_changed::<Settings>()),
+        );
+    }
+}
+
+/// The Options menu page, editing `Settings` live with mouse or keyboard,
+/// key bindings included.
+pub struct OptionsPlugin;
+
+impl Plugin for OptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OptionsCursor>()
+            .init_resource::<RebindCapture>()
+            .add_systems(
+                OnEnter(MenuScreen::Options),
+                (despawn_main_menu, spawn_options_menu),
+            )
+            .add_systems(OnExit(MenuScreen::Options), despawn_options_menu)
+            .add_systems(
+                OnTransition {
+                    from: MenuScreen::Options,
+                    to: MenuScreen::Main,
+                },
+                spawn_main_menu,
+            )
+            .add_systems(
+                Update,
+                (
+                    interact_with_options_button,
+                    (
+                        interact_with_option_buttons,
+                        interact_with_rebind_buttons,
+                        interact_with_reset_controls_button,
+                        navigate_options,
+                        capture_rebinding,
+                        update_options_menu,
+                    )
+                        .chain()
+                        .after(back_to_main_menu)
+                        .run_if(in_state(MenuScreen::Options)),
+                ),
+            );
+    }
+}
+
+/// Lifetime stats, added to after every run and saved to `stats.ron` in the
+/// data directory, and the Stats menu page that shows them.
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        let lifetime_stats: LifetimeStats = load_or_default(LifetimeStats::FILE);
+
+        app.insert_resource(lifetime_stats)
+            .add_systems(
+                OnEnter(MenuScreen::Stats),
+                (despawn_main_menu, spawn_stats_menu),
+            )
+            .add_systems(OnExit(MenuScreen::Stats), despawn_stats_menu)
+            .add_systems(
+                OnTransition {
+                    from: MenuScreen::Stats,
+                    to: MenuScreen::Main,
+                },
+                spawn_main_menu,
+            )
+            .add_systems(Update, (record_lifetime_stats, interact_with_stats_button));
+    }
+}
+
+/// Achievements from `assets/achievements.ron`, unlocked from gameplay
+/// events with a toast, saved to `unlocked_achievements.ron` in the data
+/// directory and listed on their own menu page.
+pub struct AchievementPlugin;
+
+impl Plugin for AchievementPlugin {
+    fn build(&self, app: &mut App) {
+        let path = FileAssetIo::get_base_path().join(Achievements::PATH);
+        let achievements = load_ron(&path).unwrap_or_else(|error| {
+            warn!(
+                "Could not load {}: {}, using the built-in achievements",
+                path.display(),
+                error
+            );
+            Achievements::default()
+        });
+        let unlocked: UnlockedAchievements = load_or_default(UnlockedAchievements::FILE);
+
+        app.insert_resource(achievements)
+            .insert_resource(unlocked)
+            .init_resource::<AchievementProgress>()
+            .add_systems(
+                OnEnter(MenuScreen::Achievements),
+                (despawn_main_menu, spawn_achievements_menu),
+            )
+            .add_systems(OnExit(MenuScreen::Achievements), despawn_achievements_menu)
+            .add_systems(
+                OnTransition {
+                    from: MenuScreen::Achievements,
+                    to: MenuScreen::Main,
+                },
+                spawn_main_menu,
+            )
+            .add_systems(
+                FixedUpdate,
+                time_crowd_achievements
+                    .run_if(in_state(GameState::Game))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .after(GameplaySet::Collect),
+            )
+            .add_systems(
+                Update,
+                (
+                    (track_achievement_progress, unlock_achievements).chain(),
+                    tick_toasts,
+                    interact_with_achievements_button,
+                ),
+            );
+    }
+}
+
+/// The game-over screen: the run summary with Retry, Main Menu and Save
+/// Replay, shown after the name entry when the run made the high scores.
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastRun>()
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (spawn_game_over_menu, toggle_cursor),
+            )
+            .add_systems(
+                OnExit(GameState::GameOver),
+                (despawn_game_over_menu, toggle_cursor),
+            )
+            .add_systems(
+                OnTransition {
+                    from: MenuScreen::NameEntry,
+                    to: MenuScreen::Main,
+                },
+                spawn_game_over_menu,
+            )
+            .add_systems(
+                Update,
+                (
+                    record_last_run,
+                    (
+                        interact_with_retry_button.before(GameplaySet::Start),
+                        interact_with_main_menu_button,
+                        interact_with_save_replay_button.after(finish_recording),
+                    )
+                        .run_if(in_state(GameState::GameOver)),
+                ),
+            );
+    }
+}
+
+/// Records every run and, when a `ReplayPlayback` resource is present, feeds
+/// its inputs back instead of the keyboard.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .add_systems(
+                PreUpdate,
+                apply_replay_arena.run_if(resource_exists::<ReplayPlayback>()),
+            )
+            .add_systems(
+                Update,
+                start_recording
+                    .in_set(GameplaySet::Start)
+                    .after(game_start_event),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    play_back_input.run_if(resource_exists::<ReplayPlayback>()),
+                    record_input,
+                )
+                    .chain()
+                    .in_set(GameplaySet::Input)
+                    .after(read_player_input),
+            )
+            .add_systems(
+                Update,
+                (
+                    finish_recording,
+                    finish_playback.run_if(resource_exists::<ReplayPlayback>()),
+                ),
+            );
+    }
+}
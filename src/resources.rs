@@ -1,5 +1,418 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use bevy::prelude::*;
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::components::EnemyKind;
+use crate::events::GameOver;
+use crate::utils::{load_ron, COMBO_MAX_MULTIPLIER, COMBO_WINDOW_SECONDS, SCREEN_SHAKE_SECONDS};
+
+#[derive(Resource, Default)]
+pub struct LaunchOptions {
+    pub headless: bool,
+    pub seed: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+}
+impl LaunchOptions {
+    pub fn from_args() -> LaunchOptions {
+        let mut options = LaunchOptions::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--seed" => options.seed = args.next().and_then(|seed| seed.parse().ok()),
+                "--record" => options.record = args.next().map(PathBuf::from),
+                "--replay" => options.replay = args.next().map(PathBuf::from),
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+/// The single source of randomness for a run. Reseeded on every `GameStart`,
+/// so a run is reproducible from its seed as long as systems draw from it in
+/// a fixed order.
+#[derive(Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    pub fixed_seed: Option<u64>,
+    pub rng: StdRng,
+}
+impl Default for GameRng {
+    fn default() -> GameRng {
+        GameRng::new(None)
+    }
+}
+impl GameRng {
+    pub fn new(fixed_seed: Option<u64>) -> GameRng {
+        let seed = fixed_seed.unwrap_or_else(rand::random);
+        GameRng {
+            seed,
+            fixed_seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Picks the seed for a new run (the `--seed` one, if given) and restarts
+    /// the sequence from it.
+    pub fn reseed(&mut self) {
+        self.seed = self.fixed_seed.unwrap_or_else(rand::random);
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+}
+
+/// Playing field size, in world units centred on the origin. Mirrors the
+/// primary window when there is one and stands in for it when headless.
+#[derive(Resource)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena {
+            width: 1920.0,
+            height: 1080.0,
+        }
+    }
+}
+
+/// Movement requested this tick, quantised to a byte per axis so a recorded
+/// run replays bit-for-bit, and whether a dash was asked for.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayerInput {
+    pub x: i8,
+    pub y: i8,
+    pub dash: bool,
+}
+impl PlayerInput {
+    pub fn from_direction(direction: Vec2) -> PlayerInput {
+        let direction = direction.clamp_length_max(1.0) * 127.0;
+        PlayerInput {
+            x: direction.x.round() as i8,
+            y: direction.y.round() as i8,
+            dash: false,
+        }
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, 0.0).clamp_length_max(127.0) / 127.0
+    }
+}
+
+/// Everything needed to reproduce a run: its seed, the arena it was played
+/// in and the input of every gameplay tick spent in `GameState::Game`.
+pub struct Replay {
+    pub seed: u64,
+    pub score: u32,
+    pub arena_width: f32,
+    pub arena_height: f32,
+    pub inputs: Vec<PlayerInput>,
+}
+
+const REPLAY_MAGIC: &[u8; 4] = b"TGR3";
+const REPLAY_HEADER_SIZE: usize = 28;
+const REPLAY_TICK_SIZE: usize = 3;
+
+impl Replay {
+    pub fn new(seed: u64, arena: &Arena) -> Replay {
+        Replay {
+            seed,
+            score: 0,
+            arena_width: arena.width,
+            arena_height: arena.height,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(REPLAY_HEADER_SIZE + self.inputs.len() * REPLAY_TICK_SIZE);
+        bytes.extend_from_slice(REPLAY_MAGIC);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.score.to_le_bytes());
+        bytes.extend_from_slice(&self.arena_width.to_le_bytes());
+        bytes.extend_from_slice(&self.arena_height.to_le_bytes());
+        bytes.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        for input in &self.inputs {
+            bytes.push(input.x as u8);
+            bytes.push(input.y as u8);
+            bytes.push(input.dash as u8);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Replay> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        if bytes.len() < REPLAY_HEADER_SIZE || &bytes[0..4] != REPLAY_MAGIC {
+            return Err(invalid("not a Top G replay"));
+        }
+        let seed = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        let score = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+        let arena_width = f32::from_le_bytes(bytes[16..20].try_into().unwrap());
+        let arena_height = f32::from_le_bytes(bytes[20..24].try_into().unwrap());
+        let tick_count = u32::from_le_bytes(bytes[24..28].try_into().unwrap()) as usize;
+
+        let body = &bytes[REPLAY_HEADER_SIZE..];
+        if body.len() != tick_count * REPLAY_TICK_SIZE {
+            return Err(invalid("truncated replay"));
+        }
+        let inputs = body
+            .chunks_exact(REPLAY_TICK_SIZE)
+            .map(|chunk| PlayerInput {
+                x: chunk[0] as i8,
+                y: chunk[1] as i8,
+                dash: chunk[2] != 0,
+            })
+            .collect();
+
+        Ok(Replay {
+            seed,
+            score,
+            arena_width,
+            arena_height,
+            inputs,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        Replay::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// The run in progress, and the last one to finish.
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    pub current: Option<Replay>,
+    pub last: Option<Replay>,
+}
+
+/// Present only while a replay is being played back in place of live input.
+/// `cursor` counts the gameplay ticks consumed so far.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub cursor: usize,
+}
+
+/// Lives the player spawns with at the start of each run.
+#[derive(Resource)]
+pub struct StartingLives {
+    pub value: u32,
+}
+impl Default for StartingLives {
+    fn default() -> StartingLives {
+        StartingLives { value: 3 }
+    }
+}
+
+/// One step of the difficulty curve.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WaveDefinition {
+    /// Agents spawned as the wave begins.
+    pub enemies: u32,
+    /// Seconds between the extra agents that join while the wave lasts.
+    pub reinforcement_interval: f32,
+    /// Seconds until the intermission.
+    pub duration: f32,
+    /// Multiplier on `ENEMY_SPEED`.
+    pub speed: f32,
+    /// Relative odds of spawning each kind of agent.
+    pub mix: Vec<(EnemyKind, u32)>,
+    /// Multiplier on every power-up's spawn interval.
+    pub power_up_interval: f32,
+}
+
+/// How waves keep escalating once the curve runs out.
+#[derive(Deserialize, Clone, Debug)]
+pub struct EndlessWaves {
+    pub enemies: u32,
+    pub speed: f32,
+}
+
+/// The whole difficulty curve, loaded from `assets/waves.ron` so it can be
+/// tuned without a rebuild.
+#[derive(Resource, Deserialize, Clone, Debug)]
+pub struct WaveCurve {
+    /// Seconds of calm between two waves.
+    pub intermission: f32,
+    pub waves: Vec<WaveDefinition>,
+    pub endless: EndlessWaves,
+}
+impl WaveCurve {
+    pub const PATH: &'static str = "assets/waves.ron";
+
+    pub fn load(path: &Path) -> io::Result<WaveCurve> {
+        let curve: WaveCurve = load_ron(path)?;
+        if curve.waves.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the curve needs at least one wave",
+            ));
+        }
+        Ok(curve)
+    }
+
+    /// The definition of wave `number`, counting from 1.
+    pub fn wave(&self, number: u32) -> WaveDefinition {
+        let index = (number.max(1) - 1) as usize;
+        if let Some(wave) = self.waves.get(index) {
+            return wave.clone();
+        }
+
+        let mut wave = self.waves[self.waves.len() - 1].clone();
+        let extra = (index + 1 - self.waves.len()) as u32;
+        wave.enemies += self.endless.enemies * extra;
+        wave.speed += self.endless.speed * extra as f32;
+        wave
+    }
+}
+impl Default for WaveCurve {
+    fn default() -> WaveCurve {
+        ron::from_str(include_str!("../assets/waves.ron")).expect("built-in wave curve")
+    }
+}
+
+/// What it takes to unlock an achievement. Everything but `Wave` and
+/// `Score` has to happen within a single run.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum AchievementGoal {
+    Money(u32),
+    /// Collect `count` of the power-up named `power_up`.
+    Collect {
+        power_up: String,
+        count: u32,
+    },
+    /// Collect `count` of the power-up named `power_up` in a row without
+    /// getting hit.
+    Streak {
+        power_up: String,
+        count: u32,
+    },
+    Score(u32),
+    Wave(u32),
+    Crowd {
+        agents: u32,
+        secs: f32,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Achievement {
+    /// Stays the same across releases; unlocks are saved by id.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub goal: AchievementGoal,
+}
+
+/// Every achievement there is, loaded from `assets/achievements.ron`.
+#[derive(Resource, Deserialize, Clone, Debug)]
+pub struct Achievements {
+    pub achievements: Vec<Achievement>,
+}
+impl Achievements {
+    pub const PATH: &'static str = "assets/achievements.ron";
+}
+impl Default for Achievements {
+    fn default() -> Achievements {
+        ron::from_str(include_str!("../assets/achievements.ron")).expect("built-in achievements")
+    }
+}
+
+/// How far the current run has got towards each kind of goal. Built up from
+/// gameplay events and cleared on `GameStart`.
+#[derive(Resource, Default, Debug)]
+pub struct AchievementProgress {
+    pub money: u32,
+    /// Power-ups collected, by name.
+    pub power_ups: BTreeMap<String, u32>,
+    /// Power-ups collected since the last hit, by name.
+    pub streaks: BTreeMap<String, u32>,
+    pub wave: u32,
+    /// Set once the run is over.
+    pub final_score: Option<u32>,
+    /// Seconds since the player was last hit or the crowd thinned out below
+    /// each agent count that a `Crowd` goal asks for.
+    pub crowd_secs: BTreeMap<u32, f32>,
+}
+impl AchievementProgress {
+    pub fn is_met(&self, goal: &AchievementGoal) -> bool {
+        match goal {
+            AchievementGoal::Money(money) => self.money >= *money,
+            AchievementGoal::Collect { power_up, count } => {
+                self.power_ups.get(power_up).copied().unwrap_or(0) >= *count
+            }
+            AchievementGoal::Streak { power_up, count } => {
+                self.streaks.get(power_up).copied().unwrap_or(0) >= *count
+            }
+            AchievementGoal::Score(score) => self
+                .final_score
+                .is_some_and(|final_score| final_score >= *score),
+            AchievementGoal::Wave(wave) => self.wave >= *wave,
+            AchievementGoal::Crowd { agents, secs } => self
+                .crowd_secs
+                .get(agents)
+                .is_some_and(|crowd_secs| *crowd_secs >= *secs),
+        }
+    }
+}
+
+/// The ids of the achievements unlocked on this machine, saved to
+/// `unlocked_achievements.ron` in the data directory.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct UnlockedAchievements {
+    pub ids: BTreeSet<String>,
+}
+impl UnlockedAchievements {
+    pub const FILE: &'static str = "unlocked_achievements.ron";
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavePhase {
+    Active,
+    Intermission,
+}
+
+/// Where the current run is on the `WaveCurve`.
+#[derive(Resource)]
+pub struct Wave {
+    pub number: u32,
+    pub phase: WavePhase,
+    /// Runs out when the current phase ends.
+    pub timer: Timer,
+    pub definition: WaveDefinition,
+}
+impl Wave {
+    pub fn new(number: u32, curve: &WaveCurve) -> Wave {
+        let definition = curve.wave(number);
+        Wave {
+            number,
+            phase: WavePhase::Active,
+            timer: Timer::from_seconds(definition.duration, TimerMode::Once),
+            definition,
+        }
+    }
+}
+impl FromWorld for Wave {
+    fn from_world(world: &mut World) -> Wave {
+        Wave::new(1, world.resource::<WaveCurve>())
+    }
+}
 
 #[derive(Resource)]
 pub struct Enemies {
@@ -11,13 +424,59 @@ impl Default for Enemies {
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct Score {
     pub value: u32,
 }
-impl Default for Score {
-    fn default() -> Score {
-        Score { value: 0 }
+
+/// The money combo. Money collected while `timer` runs raises the multiplier;
+/// when it runs out the multiplier drops a step and, if still above x1, the
+/// window opens again.
+#[derive(Resource, Debug)]
+pub struct Combo {
+    pub multiplier: u32,
+    pub timer: Timer,
+}
+impl Default for Combo {
+    fn default() -> Combo {
+        let mut timer = Timer::from_seconds(COMBO_WINDOW_SECONDS, TimerMode::Once);
+        timer.tick(timer.duration());
+        Combo {
+            multiplier: 1,
+            timer,
+        }
+    }
+}
+impl Combo {
+    /// Counts a pickup and returns the points it is worth.
+    pub fn collect(&mut self) -> u32 {
+        if !self.timer.finished() {
+            self.multiplier = (self.multiplier + 1).min(COMBO_MAX_MULTIPLIER);
+        }
+        self.timer.reset();
+        self.multiplier
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        self.timer.tick(delta);
+        if self.timer.just_finished() && self.multiplier > 1 {
+            self.multiplier -= 1;
+            if self.multiplier > 1 {
+                self.timer.reset();
+            }
+        }
+    }
+}
+
+/// Multiplier on the time agents see, for their movement and the
+/// reinforcement timer.
+#[derive(Resource, PartialEq)]
+pub struct EnemyTimeScale {
+    pub value: f32,
+}
+impl Default for EnemyTimeScale {
+    fn default() -> EnemyTimeScale {
+        EnemyTimeScale { value: 1.0 }
     }
 }
 
@@ -33,9 +492,208 @@ impl Default for SpawnEnemyTimer {
     }
 }
 
+/// Everything the player can do with a key or gamepad button. Systems read
+/// these through `Input<Action>` rather than looking at `KeyCode`s or
+/// `GamepadButton`s themselves.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Dash,
+    /// Pauses the game, or steps back out of a menu page.
+    Pause,
+    ToggleFps,
+    /// Activates the highlighted menu entry.
+    Confirm,
+}
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Dash,
+        Action::Pause,
+        Action::ToggleFps,
+        Action::Confirm,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move Up",
+            Action::MoveDown => "Move Down",
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::Dash => "Dash",
+            Action::Pause => "Pause",
+            Action::ToggleFps => "Toggle FPS",
+            Action::Confirm => "Confirm",
+        }
+    }
+
+    pub fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::MoveUp => vec![KeyCode::Up, KeyCode::W],
+            Action::MoveDown => vec![KeyCode::Down, KeyCode::S],
+            Action::MoveLeft => vec![KeyCode::Left, KeyCode::A],
+            Action::MoveRight => vec![KeyCode::Right, KeyCode::D],
+            Action::Dash => vec![KeyCode::ShiftLeft],
+            Action::Pause => vec![KeyCode::Escape],
+            Action::ToggleFps => vec![KeyCode::F],
+            Action::Confirm => vec![KeyCode::Return, KeyCode::Space],
+        }
+    }
+
+    /// Gamepad buttons are fixed; only keys can be rebound.
+    pub fn gamepad_buttons(&self) -> &'static [GamepadButtonType] {
+        match self {
+            Action::MoveUp => &[GamepadButtonType::DPadUp],
+            Action::MoveDown => &[GamepadButtonType::DPadDown],
+            Action::MoveLeft => &[GamepadButtonType::DPadLeft],
+            Action::MoveRight => &[GamepadButtonType::DPadRight],
+            Action::Dash => &[GamepadButtonType::East, GamepadButtonType::RightTrigger],
+            Action::Pause => &[GamepadButtonType::Start],
+            Action::ToggleFps => &[GamepadButtonType::Select],
+            Action::Confirm => &[GamepadButtonType::South],
+        }
+    }
+}
+
+/// Which keys trigger which `Action`. Actions missing from the map use their
+/// default keys, so older settings files pick up new actions.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Bindings {
+    pub keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+impl Bindings {
+    pub fn keys(&self, action: Action) -> Vec<KeyCode> {
+        self.keys
+            .get(&action)
+            .cloned()
+            .unwrap_or_else(|| action.default_keys())
+    }
+
+    /// Makes `key` the only key for `action`, taking it away from any other
+    /// action that used it.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        for other in Action::ALL {
+            if other != action {
+                let mut keys = self.keys(other);
+                if keys.contains(&key) {
+                    keys.retain(|bound| *bound != key);
+                    self.keys.insert(other, keys);
+                }
+            }
+        }
+        self.keys.insert(action, vec![key]);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    BorderlessFullscreen,
+    Fullscreen,
+}
+
+/// How the player steers: with keys, D-pad and stick, or by chasing the
+/// mouse cursor.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ControlMode {
+    #[default]
+    Keyboard,
+    Mouse,
+}
+
+/// Where the cursor points in the arena, or `None` while it is outside the
+/// window.
+#[derive(Resource, Default)]
+pub struct MouseTarget {
+    pub position: Option<Vec2>,
+}
+
+/// Set on the frame the dash action goes down and cleared by the next
+/// gameplay tick, so a tap between two ticks still dashes.
+#[derive(Resource, Default)]
+pub struct DashRequested {
+    pub value: bool,
+}
+
+/// Player preferences, saved to `settings.ron` in the data directory whenever
+/// they change. Fields missing from the file keep their defaults.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub display_mode: DisplayMode,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    pub show_fps: bool,
+    pub screen_shake: bool,
+    pub music: bool,
+    /// Volumes run from 0.0 to 1.0.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub bindings: Bindings,
+    pub control_mode: ControlMode,
+}
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            display_mode: DisplayMode::Fullscreen,
+            resolution: (1920, 1080),
+            vsync: true,
+            show_fps: true,
+            screen_shake: true,
+            music: true,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            bindings: Bindings::default(),
+            control_mode: ControlMode::default(),
+        }
+    }
+}
+impl Settings {
+    pub const FILE: &'static str = "settings.ron";
+}
+
+/// The highlighted row of the Options page, for keyboard navigation.
+#[derive(Resource, Default)]
+pub struct OptionsCursor {
+    pub index: usize,
+}
+
+/// The button highlighted for keyboard and gamepad navigation on the other
+/// menu pages. `None` until the player first moves the highlight.
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    pub button: Option<Entity>,
+}
+
+/// The action waiting for a key on the Options page, if any.
+#[derive(Resource, Default)]
+pub struct RebindCapture {
+    pub action: Option<Action>,
+}
+
+/// Shakes the camera until the timer runs out.
+#[derive(Resource)]
+pub struct ScreenShake {
+    pub timer: Timer,
+}
+impl Default for ScreenShake {
+    fn default() -> ScreenShake {
+        let mut timer = Timer::from_seconds(SCREEN_SHAKE_SECONDS, TimerMode::Once);
+        timer.tick(timer.duration());
+        ScreenShake { timer }
+    }
+}
+
 #[derive(Resource)]
 pub struct FpsTracker {
-    pub enabled: bool,
     pub fps: u32,
     pub frame_time: f32,
     pub frame_count: u32,
@@ -44,7 +702,6 @@ pub struct FpsTracker {
 impl Default for FpsTracker {
     fn default() -> Self {
         Self {
-            enabled: true,
             fps: 0,
             frame_time: 0.0,
             frame_count: 0,
@@ -71,6 +728,156 @@ pub enum GameState {
     Menu,
     Paused,
     Game,
+    /// The run summary, between the last life and a retry or the main menu.
+    GameOver,
+}
+
+/// Which page of the menu is showing while in `GameState::Menu` or `Paused`.
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum MenuScreen {
+    #[default]
+    Main,
+    HighScores,
+    Options,
+    Stats,
+    Achievements,
+    /// Typing a name for a run that made the high-score table.
+    NameEntry,
+}
+
+/// What happened this run, beyond the score. Reset on `GameStart`.
+#[derive(Resource, Default, Debug, Clone, PartialEq)]
+pub struct RunStats {
+    pub money: u32,
+    /// Power-ups collected, by name.
+    pub power_ups: BTreeMap<String, u32>,
+    pub hits: u32,
+    pub near_misses: u32,
+    /// Pixels the player moved, knockback included.
+    pub distance: f32,
+    pub invincible_secs: f32,
+}
+impl RunStats {
+    pub fn count_power_up(&mut self, name: &str) {
+        *self.power_ups.entry(name.to_string()).or_default() += 1;
+    }
+}
+
+/// Every finished run added up, saved to `stats.ron` in the data directory.
+/// Fields missing from the file start from zero.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub runs: u32,
+    pub total_score: u64,
+    pub money: u64,
+    /// Power-ups collected, by name.
+    pub power_ups: BTreeMap<String, u64>,
+    pub hits: u64,
+    pub near_misses: u64,
+    pub distance: f64,
+    pub survival_secs: f64,
+    pub invincible_secs: f64,
+    pub best_score: u32,
+    pub best_wave: u32,
+    pub longest_survival_secs: f32,
+    pub longest_distance: f32,
+    pub most_near_misses: u32,
+}
+impl LifetimeStats {
+    pub const FILE: &'static str = "stats.ron";
+
+    pub fn record(&mut self, game_over: &GameOver, run_stats: &RunStats) {
+        self.runs += 1;
+        self.total_score += game_over.score as u64;
+        self.money += run_stats.money as u64;
+        for (name, count) in &run_stats.power_ups {
+            *self.power_ups.entry(name.clone()).or_default() += *count as u64;
+        }
+        self.hits += run_stats.hits as u64;
+        self.near_misses += run_stats.near_misses as u64;
+        self.distance += run_stats.distance as f64;
+        self.survival_secs += game_over.survival_secs as f64;
+        self.invincible_secs += run_stats.invincible_secs as f64;
+
+        self.best_score = self.best_score.max(game_over.score);
+        self.best_wave = self.best_wave.max(game_over.wave);
+        self.longest_survival_secs = self.longest_survival_secs.max(game_over.survival_secs);
+        self.longest_distance = self.longest_distance.max(run_stats.distance);
+        self.most_near_misses = self.most_near_misses.max(run_stats.near_misses);
+    }
+
+    pub fn average_score(&self) -> f32 {
+        self.total_score as f32 / self.runs.max(1) as f32
+    }
+
+    pub fn average_survival_secs(&self) -> f32 {
+        (self.survival_secs / self.runs.max(1) as f64) as f32
+    }
+}
+
+/// What the game-over screen shows: the summary of the run that just ended
+/// and the best score before it.
+#[derive(Resource, Default)]
+pub struct LastRun {
+    pub summary: Option<GameOver>,
+    pub best_before: Option<u32>,
+}
+
+/// Time spent in `GameState::Game` this run, counted in gameplay ticks.
+#[derive(Resource, Default)]
+pub struct SurvivalTime {
+    pub value: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub wave: u32,
+    pub seed: u64,
+    pub survival_secs: f32,
+    /// Seconds since the Unix epoch when the run ended.
+    pub timestamp: u64,
+}
+
+/// The best runs on this machine, best first, saved to `high_scores.ron` in
+/// the data directory.
+#[derive(Resource, Serialize, Deserialize, Default, Debug)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+impl HighScores {
+    pub const CAPACITY: usize = 10;
+    pub const FILE: &'static str = "high_scores.ron";
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < HighScores::CAPACITY
+                || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    /// Adds `entry` below any equal scores and returns its rank, counting
+    /// from 0, or `None` if it did not make the table.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self
+            .entries
+            .iter()
+            .position(|existing| entry.score > existing.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(HighScores::CAPACITY);
+        Some(rank)
+    }
+}
+
+/// A run that made the table and is waiting for a name.
+#[derive(Resource)]
+pub struct PendingHighScore {
+    pub entry: HighScore,
 }
 
 #[derive(Resource)]
@@ -83,41 +890,133 @@ impl Default for FirstGame {
     }
 }
 
-#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
-pub enum Invincible {
-    #[default]
-    Off,
-    On,
+/// What collecting a power-up does.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PowerUpEffect {
+    /// Scatters this much money across the arena at once.
+    MoneyBurst { stars: usize },
+    /// Agents pass straight through the player while it lasts.
+    Invincibility,
+    /// Money within `radius` pixels flies to the player while it lasts.
+    Magnet { radius: f32 },
+    /// Agents and their reinforcements run at `scale` times normal speed
+    /// while it lasts. The player is unaffected.
+    TimeSlow { scale: f32 },
+    /// Adds a shield that takes the next hit in place of a life, holding at
+    /// most `cap` at a time.
+    Shield { cap: u32 },
 }
 
-#[derive(Resource)]
-pub struct SpawnInvinciTimer {
-    pub timer: Timer,
-    pub rng: StdRng,
+/// A track that takes over from the background music for `secs` seconds.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct MusicOverride {
+    pub track: String,
+    pub secs: f32,
 }
 
-impl Default for SpawnInvinciTimer {
-    fn default() -> Self {
-        let mut rng = StdRng::from_entropy();
-        let random_time = rng.gen_range(0.0..240.0);
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct PowerUp {
+    /// Shown on the stats pages, and how achievements and saved stats refer
+    /// to it.
+    pub name: String,
+    pub sprite: String,
+    /// Shortest and longest wait, in seconds, before the next one appears.
+    pub spawn_interval: (f32, f32),
+    /// Seconds the effect lasts, 0 for effects that happen all at once.
+    pub duration: f32,
+    #[serde(default)]
+    pub music: Option<MusicOverride>,
+    pub effect: PowerUpEffect,
+}
 
-        SpawnInvinciTimer {
-            timer: Timer::from_seconds(random_time, TimerMode::Repeating),
-            rng,
-        }
+/// Every power-up there is, loaded from `assets/power_ups.ron`.
+#[derive(Resource, Deserialize, Clone, Debug)]
+pub struct PowerUps {
+    pub power_ups: Vec<PowerUp>,
+}
+impl PowerUps {
+    pub const PATH: &'static str = "assets/power_ups.ron";
+
+    pub fn get(&self, name: &str) -> Option<&PowerUp> {
+        self.power_ups.iter().find(|power_up| power_up.name == name)
+    }
+}
+impl Default for PowerUps {
+    fn default() -> PowerUps {
+        ron::from_str(include_str!("../assets/power_ups.ron")).expect("built-in power-ups")
     }
 }
 
-#[derive(Resource)]
-pub struct InvinciDurationTimer {
+/// Time until each kind of power-up next appears.
+#[derive(Resource, Default)]
+pub struct PowerUpTimers {
+    pub timers: BTreeMap<String, Timer>,
+}
+
+pub struct ActivePowerUp {
+    pub effect: PowerUpEffect,
     pub timer: Timer,
 }
-impl Default for InvinciDurationTimer {
-    fn default() -> InvinciDurationTimer {
-        InvinciDurationTimer {
-            timer: Timer::from_seconds(30.0, TimerMode::Repeating),
+
+/// Power-ups whose effect is still running.
+#[derive(Resource, Default)]
+pub struct ActivePowerUps {
+    pub active: BTreeMap<String, ActivePowerUp>,
+}
+impl ActivePowerUps {
+    /// Starts the effect of `power_up`, or starts it over if it is already
+    /// running. Effects without a duration are over as soon as they happen.
+    pub fn activate(&mut self, power_up: &PowerUp) {
+        if power_up.duration > 0.0 {
+            self.active.insert(
+                power_up.name.clone(),
+                ActivePowerUp {
+                    effect: power_up.effect,
+                    timer: Timer::from_seconds(power_up.duration, TimerMode::Once),
+                },
+            );
         }
     }
+
+    pub fn is_active(&self, name: &str) -> bool {
+        self.active.contains_key(name)
+    }
+
+    pub fn invincible(&self) -> bool {
+        self.active
+            .values()
+            .any(|active| active.effect == PowerUpEffect::Invincibility)
+    }
+
+    /// The radius of the strongest magnet running, if any.
+    pub fn magnet_radius(&self) -> Option<f32> {
+        self.active
+            .values()
+            .filter_map(|active| match active.effect {
+                PowerUpEffect::Magnet { radius } => Some(radius),
+                _ => None,
+            })
+            .reduce(f32::max)
+    }
+
+    /// How fast agents run, 1 unless a time-slow is active.
+    pub fn enemy_time_scale(&self) -> f32 {
+        self.active
+            .values()
+            .filter_map(|active| match active.effect {
+                PowerUpEffect::TimeSlow { scale } => Some(scale),
+                _ => None,
+            })
+            .fold(1.0, f32::min)
+    }
+
+    /// Runs every effect's clock, dropping the ones that have run out.
+    pub fn tick(&mut self, delta: Duration) {
+        self.active.retain(|_kind, active| {
+            active.timer.tick(delta);
+            !active.timer.finished()
+        });
+    }
 }
 
 #[derive(Resource)]
@@ -150,21 +1049,3 @@ pub enum MrProducerState {
     #[default]
     On,
 }
-
-#[derive(Resource)]
-pub struct SpawnCoffeeTimer {
-    pub timer: Timer,
-    pub rng: StdRng,
-}
-
-impl Default for SpawnCoffeeTimer {
-    fn default() -> Self {
-        let mut rng = StdRng::from_entropy();
-        let random_time = rng.gen_range(0.0..90.0);
-
-        SpawnCoffeeTimer {
-            timer: Timer::from_seconds(random_time, TimerMode::Repeating),
-            rng,
-        }
-    }
-}
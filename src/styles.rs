@@ -1,4 +1,6 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::prelude::*;
+
+use crate::resources::*;
 
 pub const NORMAL_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
@@ -29,15 +31,13 @@ pub const RAINBOW_COLORS: [Color; 22] = [
     Color::rgb(1.0, 0.5, 1.0),   // Light Violet
 ];
 
-pub fn main_menu_style(window_query: Query<&Window, With<PrimaryWindow>>) -> Style {
-    let window = window_query.get_single().unwrap();
-
+pub fn main_menu_style(arena: &Arena) -> Style {
     Style {
         flex_direction: FlexDirection::Column,
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         margin: UiRect {
-            left: Val::Px((window.width() / 2.0) - 180.0),
+            left: Val::Px((arena.width / 2.0) - 180.0),
             right: Val::Px(0.0),
             top: Val::Px(0.0),
             bottom: Val::Px(0.0),
@@ -55,24 +55,22 @@ pub fn button_style() -> Style {
     }
 }
 
-pub fn mr_producer_button_style(window: &Window) -> Style {
+pub fn mr_producer_button_style(arena: &Arena) -> Style {
     let half_button_width = 60.0;
 
     Style {
         width: Val::Px(165.0),
         height: Val::Px(50.0),
         position_type: PositionType::Absolute,
-        left: Val::Px((window.width() / 2.0) - half_button_width), // Position at 50% to the left, which is center horizontally
-        bottom: Val::Px(0.0), // Position at the very bottom
+        left: Val::Px((arena.width / 2.0) - half_button_width), // Position at 50% to the left, which is center horizontally
+        bottom: Val::Px(0.0),                                   // Position at the very bottom
         justify_content: JustifyContent::Center, // This will center your content if it's smaller than your button
-        align_items: AlignItems::Center, // This will center your content vertically
+        align_items: AlignItems::Center,         // This will center your content vertically
         border: UiRect::all(Val::Px(5.0)),
         ..default()
     }
 }
 
-
-
 pub fn image_style() -> Style {
     Style {
         margin: UiRect::new(Val::Px(8.0), Val::Px(8.0), Val::Px(8.0), Val::Px(8.0)),
@@ -105,7 +103,6 @@ pub fn get_shadow_text_style(asset_server: &Res<AssetServer>) -> TextStyle {
     }
 }
 
-
 pub fn get_score_text_style(asset_server: &Res<AssetServer>) -> TextStyle {
     TextStyle {
         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
@@ -120,4 +117,25 @@ pub fn get_button_text_style(asset_server: &Res<AssetServer>) -> TextStyle {
         font_size: 32.0,
         color: Color::WHITE,
     }
-}
\ No newline at end of file
+}
+
+pub const SELECTED_TEXT_COLOR: Color = Color::rgb(1.0, 0.85, 0.2);
+
+pub fn option_row_style() -> Style {
+    Style {
+        flex_direction: FlexDirection::Row,
+        justify_content: JustifyContent::SpaceBetween,
+        align_items: AlignItems::Center,
+        width: Val::Px(520.0),
+        margin: UiRect::vertical(Val::Px(4.0)),
+        ..default()
+    }
+}
+
+pub fn option_buttons_style() -> Style {
+    Style {
+        flex_direction: FlexDirection::Row,
+        column_gap: Val::Px(8.0),
+        ..default()
+    }
+}
@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use crate::components::*;
 use crate::events::*;
@@ -8,31 +11,85 @@ use crate::utils::*;
 use bevy::{
     app::AppExit,
     prelude::*,
+    sprite::Anchor,
     window::{CursorGrabMode, PrimaryWindow},
 };
-use rand::prelude::*;
+use rand::Rng;
+
+/// Turns the cursor position into arena coordinates for mouse steering. The
+/// camera sits at the origin, so this ignores any screen shake.
+pub fn update_mouse_target(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut mouse_target: ResMut<MouseTarget>,
+) {
+    if let Ok(window) = window_query.get_single() {
+        mouse_target.position = window.cursor_position().map(|cursor| {
+            Vec2::new(
+                cursor.x - window.width() / 2.0,
+                window.height() / 2.0 - cursor.y,
+            )
+        });
+    }
+}
 
 pub fn setup_cursor(mut windows: Query<&mut Window>) {
-    let mut window = windows.single_mut();
-    window.cursor.visible = false;
-    window.cursor.grab_mode = CursorGrabMode::Locked;
+    if let Ok(mut window) = windows.get_single_mut() {
+        window.cursor.visible = false;
+        window.cursor.grab_mode = CursorGrabMode::Locked;
+    }
 }
 
-pub fn toggle_cursor(mut windows: Query<&mut Window>) {
-    let mut window = windows.single_mut();
+/// Frees the cursor for menus and captures it again for play. Mouse steering
+/// keeps it visible and confined to the window rather than locked, so its
+/// position keeps updating.
+pub fn toggle_cursor(mut windows: Query<&mut Window>, settings: Res<Settings>) {
+    if let Ok(mut window) = windows.get_single_mut() {
+        let mouse = settings.control_mode == ControlMode::Mouse;
+        window.cursor.grab_mode = match window.cursor.grab_mode {
+            CursorGrabMode::None if mouse => CursorGrabMode::Confined,
+            CursorGrabMode::None => CursorGrabMode::Locked,
+            CursorGrabMode::Locked | CursorGrabMode::Confined => CursorGrabMode::None,
+        };
+        window.cursor.visible = mouse || window.cursor.grab_mode == CursorGrabMode::None;
+    }
+}
 
-    window.cursor.visible = !window.cursor.visible;
-    window.cursor.grab_mode = match window.cursor.grab_mode {
-        CursorGrabMode::None => CursorGrabMode::Locked,
-        CursorGrabMode::Locked | CursorGrabMode::Confined => CursorGrabMode::None,
-    };
+pub fn update_arena(window_query: Query<&Window, With<PrimaryWindow>>, mut arena: ResMut<Arena>) {
+    if let Ok(window) = window_query.get_single() {
+        if arena.width != window.width() || arena.height != window.height() {
+            arena.width = window.width();
+            arena.height = window.height();
+        }
+    }
+}
+
+pub fn start_game(mut game_start_event_writer: EventWriter<GameStart>) {
+    game_start_event_writer.send(GameStart {});
+}
+
+pub fn exit_headless_game(
+    mut reader: EventReader<GameOver>,
+    mut app_exit_event_writer: EventWriter<AppExit>,
+    game_rng: Res<GameRng>,
+) {
+    if let Some(game_over) = reader.iter().last() {
+        println!(
+            "Game over! Score: ${}, wave {} (seed {})",
+            game_over.score, game_over.wave, game_rng.seed
+        );
+        app_exit_event_writer.send(AppExit);
+    }
 }
 
 pub fn spawn_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle { ..default() });
 }
 
-pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn spawn_player(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    starting_lives: Res<StartingLives>,
+) {
     commands.spawn((
         SpriteBundle {
             transform: Transform::from_xyz(0.0, 0.0, 0.0),
@@ -43,6 +100,12 @@ pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
             timer: Timer::from_seconds(0.1, TimerMode::Repeating),
             color_index: 22,
         },
+        Lives {
+            value: starting_lives.value,
+        },
+        Dash::default(),
+        Shields::default(),
+        Interpolated::new(Vec3::ZERO),
     ));
 }
 
@@ -65,11 +128,11 @@ pub fn spawn_rainbow_player(mut commands: Commands, asset_server: Res<AssetServe
 
 pub fn update_player_colors(
     time: Res<Time>,
-    mut query: Query<(&mut Player, &mut Sprite)>,
-    invinci_state: Res<State<Invincible>>,
+    mut query: Query<(&mut Player, &mut Sprite, Option<&Recovering>)>,
+    active_power_ups: Res<ActivePowerUps>,
 ) {
-    for (mut player, mut sprite) in query.iter_mut() {
-        if *invinci_state.get() == Invincible::On {
+    for (mut player, mut sprite, recovering) in query.iter_mut() {
+        if active_power_ups.invincible() {
             player.timer.tick(time.delta());
             if player.timer.finished() {
                 player.color_index = (player.color_index + 1) % RAINBOW_COLORS.len();
@@ -78,53 +141,192 @@ pub fn update_player_colors(
         } else {
             sprite.color = RAINBOW_COLORS[0]
         }
+
+        if let Some(recovering) = recovering {
+            let blink = (recovering.timer.elapsed_secs() / BLINK_SECONDS) as u32 % 2 == 1;
+            sprite.color.set_a(if blink { 0.2 } else { 1.0 });
+        }
     }
 }
 
 pub fn game_start_event(
     mut commands: Commands,
     mut enemy_query: Query<(Entity, &Transform), With<Enemy>>,
+    pickup_query: Query<Entity, Or<(With<Star>, With<PowerUpPickup>)>>,
     mut score: ResMut<Score>,
     mut reader: EventReader<GameStart>,
     mut game_state: ResMut<NextState<GameState>>,
     mut number_of_enemies: ResMut<Enemies>,
+    mut first_game: ResMut<FirstGame>,
+    mut survival_time: ResMut<SurvivalTime>,
+    mut run_stats: ResMut<RunStats>,
+    mut combo: ResMut<Combo>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     if let Some(_game_start) = reader.iter().last() {
+        survival_time.value = Duration::ZERO;
+        *run_stats = RunStats::default();
+        *combo = Combo::default();
         for (enemy_entity, _enemy_transform) in enemy_query.iter_mut() {
             commands.entity(enemy_entity).despawn()
         }
+        for pickup_entity in pickup_query.iter() {
+            commands.entity(pickup_entity).despawn();
+        }
         number_of_enemies.value = 3;
         score.value = 0;
+        first_game.value = false;
+        game_rng.reseed();
         game_state.set(GameState::Game);
     }
 }
 
+pub fn spawn_initial_enemies(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    mut reader: EventReader<GameStart>,
+    player_query: Query<&Transform, With<Player>>,
+    wave: Res<Wave>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    if let Some(_game_start) = reader.iter().last() {
+        for _ in 0..wave.definition.enemies {
+            let kind = pick_enemy_kind(&wave.definition.mix, &mut game_rng);
+            spawn_enemy(
+                &mut commands,
+                &asset_server,
+                &arena,
+                &mut game_rng,
+                player_query.get_single().ok(),
+                kind,
+            );
+        }
+    }
+}
+
+pub fn reset_waves(
+    mut reader: EventReader<GameStart>,
+    wave_curve: Res<WaveCurve>,
+    mut wave: ResMut<Wave>,
+    mut enemy_spawn_timer: ResMut<SpawnEnemyTimer>,
+    mut wave_started_event_writer: EventWriter<WaveStarted>,
+) {
+    if let Some(_game_start) = reader.iter().last() {
+        *wave = Wave::new(1, &wave_curve);
+        wave_started_event_writer.send(WaveStarted { number: 1 });
+        enemy_spawn_timer
+            .timer
+            .set_duration(Duration::from_secs_f32(
+                wave.definition.reinforcement_interval,
+            ));
+        enemy_spawn_timer.timer.reset();
+    }
+}
+
+/// Ends the current wave once its time is up, clearing the arena for an
+/// intermission, and starts the next one when the intermission is over.
+pub fn advance_wave(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    wave_curve: Res<WaveCurve>,
+    mut wave: ResMut<Wave>,
+    mut enemy_spawn_timer: ResMut<SpawnEnemyTimer>,
+    enemy_query: Query<Entity, With<Enemy>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut game_rng: ResMut<GameRng>,
+    fixed_time: Res<FixedTime>,
+    mut wave_started_event_writer: EventWriter<WaveStarted>,
+) {
+    wave.timer.tick(fixed_time.period);
+    if !wave.timer.just_finished() {
+        return;
+    }
+
+    match wave.phase {
+        WavePhase::Active => {
+            for enemy_entity in enemy_query.iter() {
+                commands.entity(enemy_entity).despawn();
+            }
+            wave.phase = WavePhase::Intermission;
+            wave.timer = Timer::from_seconds(wave_curve.intermission, TimerMode::Once);
+        }
+        WavePhase::Intermission => {
+            *wave = Wave::new(wave.number + 1, &wave_curve);
+            wave_started_event_writer.send(WaveStarted {
+                number: wave.number,
+            });
+            enemy_spawn_timer
+                .timer
+                .set_duration(Duration::from_secs_f32(
+                    wave.definition.reinforcement_interval,
+                ));
+            enemy_spawn_timer.timer.reset();
+
+            for _ in 0..wave.definition.enemies {
+                let kind = pick_enemy_kind(&wave.definition.mix, &mut game_rng);
+                spawn_enemy(
+                    &mut commands,
+                    &asset_server,
+                    &arena,
+                    &mut game_rng,
+                    player_query.get_single().ok(),
+                    kind,
+                );
+            }
+        }
+    }
+}
+
+pub fn reset_pickup_timers(
+    mut reader: EventReader<GameStart>,
+    wave: Res<Wave>,
+    mut game_rng: ResMut<GameRng>,
+    power_ups: Res<PowerUps>,
+    mut power_up_timers: ResMut<PowerUpTimers>,
+    mut active_power_ups: ResMut<ActivePowerUps>,
+) {
+    if let Some(_game_start) = reader.iter().last() {
+        for power_up in &power_ups.power_ups {
+            let delay = power_up_delay(power_up, &wave.definition, &mut game_rng);
+            power_up_timers
+                .timers
+                .insert(power_up.name.clone(), Timer::new(delay, TimerMode::Once));
+        }
+        active_power_ups.active.clear();
+    }
+}
+
+pub fn tick_survival_time(mut survival_time: ResMut<SurvivalTime>, fixed_time: Res<FixedTime>) {
+    survival_time.value += fixed_time.period;
+}
+
 pub fn game_over_event_receiver(
     mut reader: EventReader<GameOver>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
     if let Some(_game_over) = reader.iter().last() {
-        game_state.set(GameState::Menu);
+        game_state.set(GameState::GameOver);
     }
 }
 pub fn draw_enemy_number(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     number_of_enemies: ResMut<Enemies>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     enemy_number_query: Query<Entity, With<DrawEnemyNumber>>,
 ) {
     for enemy_number_entity in enemy_number_query.iter() {
         commands.entity(enemy_number_entity).despawn();
     }
 
-    let window = window_query.get_single().unwrap();
-    let x = window.width() / 64.0;
-    let y = window.height() / 2.0 - 30.0;
+    let x = arena.width / 64.0;
+    let y = arena.height / 2.0 - 30.0;
 
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     let text_style = TextStyle {
-        font: font,
+        font,
         font_size: 30.0,
         color: Color::WHITE,
     };
@@ -141,13 +343,14 @@ pub fn draw_enemy_number(
 
 pub fn spawn_enemies(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>,
     enemy_spawn_timer: ResMut<SpawnEnemyTimer>,
     mut number_of_enemies: ResMut<Enemies>,
     mut enemy_query: Query<(&mut Transform, &mut Enemy), Without<Player>>,
-    mut reader: EventReader<GameStart>,
     player_query: Query<&Transform, With<Player>>,
+    wave: Res<Wave>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     let mut current_enemies: u32 = 0;
     for (_enemy_entity, _enemy_transform) in enemy_query.iter_mut() {
@@ -155,338 +358,437 @@ pub fn spawn_enemies(
     }
     number_of_enemies.value = current_enemies;
 
-    let mut game_start: bool = false;
-    if let Some(_game_start) = reader.iter().last() {
-        game_start = true
-    }
-    let iterations: i32;
-    if game_start {
-        iterations = 4
-    } else {
-        iterations = 1
-    }
-
-    if enemy_spawn_timer.timer.finished() || game_start {
-        for _ in 0..iterations {
-            let window = window_query.get_single().unwrap();
-            let width = (window.width() / 2.0) - (ENEMY_SIZE / 2.0);
-            let height = (window.height() / 2.0) - (ENEMY_SIZE / 2.0);
-
-            let edge = rand::random::<u8>() % 4;
-            let (mut random_x, mut random_y) = match edge {
-                0 => ((random::<f32>() * width * 2.0) - width, height),
-                1 => ((random::<f32>() * width * 2.0) - width, -height),
-                2 => (width, (random::<f32>() * height * 2.0) - height),
-                _ => (-width, (random::<f32>() * height * 2.0) - height),
-            };
-
-            if let Ok(player_transform) = player_query.get_single() {
-                let player_x: f32 = player_transform.translation.x;
-                let player_y: f32 = player_transform.translation.y;
-
-                while is_collision(random_x, random_y, player_x, player_y) {
-                    let (new_x, new_y) = match edge {
-                        0 => ((random::<f32>() * width * 2.0) - width, height),
-                        1 => ((random::<f32>() * width * 2.0) - width, -height),
-                        2 => (width, (random::<f32>() * height * 2.0) - height),
-                        _ => (-width, (random::<f32>() * height * 2.0) - height),
-                    };
-                    random_x = new_x;
-                    random_y = new_y;
-                }
-            }
-
-            commands.spawn((
-                SpriteBundle {
-                    transform: Transform::from_xyz(random_x, random_y, 0.0),
-                    texture: asset_server.load("sprites/agent.png"),
-                    ..default()
-                },
-                Enemy {
-                    direction: Vec2::new(random::<f32>(), random::<f32>()).normalize(),
-                },
-            ));
-        }
+    if wave.phase == WavePhase::Active && enemy_spawn_timer.timer.just_finished() {
+        let kind = pick_enemy_kind(&wave.definition.mix, &mut game_rng);
+        spawn_enemy(
+            &mut commands,
+            &asset_server,
+            &arena,
+            &mut game_rng,
+            player_query.get_single().ok(),
+            kind,
+        );
     }
 }
 
 pub fn spawn_stars(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>,
     mut star_query: Query<(Entity, &Transform), With<Star>>,
-    mut coffee_query: Query<&mut Coffee, With<Coffee>>,
+    mut power_up_reader: EventReader<PowerUpCollected>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     let mut current_stars: usize = 0;
     for (_star_entity, _star_transform) in star_query.iter_mut() {
         current_stars += 1;
     }
 
-    let coffee_star_bonus: usize = NUMBER_OF_STARS * 3;
-    let mut star_number: usize = if current_stars > NUMBER_OF_STARS {
-        0
-    } else {
-        NUMBER_OF_STARS - current_stars
-    };
+    let mut star_number: usize = NUMBER_OF_STARS.saturating_sub(current_stars);
 
-    for mut coffee in coffee_query.iter_mut() {
-        if coffee.collected {
-            coffee.collected = false;
-            star_number = coffee_star_bonus;
+    for power_up_collected in power_up_reader.iter() {
+        if let PowerUpEffect::MoneyBurst { stars } = power_up_collected.effect {
+            star_number = stars;
         }
     }
 
     for _ in 0..(star_number) {
-        let window = window_query.get_single().unwrap();
-        let width = (window.width() / 2.0) - (ENEMY_SIZE / 2.0);
-        let height = (window.height() / 2.0) - (ENEMY_SIZE / 2.0);
-
-        let random_x = (random::<f32>() * width * 2.0) - width;
-        let random_y = (random::<f32>() * height * 2.0) - height;
+        let position = random_arena_position(&arena, &mut game_rng);
 
         commands.spawn((
             SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
+                transform: Transform::from_translation(position.extend(0.0)),
                 texture: asset_server.load("sprites/money.png"),
                 ..default()
             },
+            Interpolated::new(position.extend(0.0)),
             Star {},
         ));
     }
 }
 
-pub fn spawn_coffee(
+/// Spawns each kind of power-up when its timer runs out, unless one is
+/// already waiting on the field or its effect is still running.
+pub fn spawn_power_ups(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>,
-    mut coffee_spawn_timer: ResMut<SpawnCoffeeTimer>,
-    time: Res<Time>,
+    power_ups: Res<PowerUps>,
+    mut power_up_timers: ResMut<PowerUpTimers>,
+    pickup_query: Query<&PowerUpPickup>,
+    active_power_ups: Res<ActivePowerUps>,
+    wave: Res<Wave>,
+    mut game_rng: ResMut<GameRng>,
+    fixed_time: Res<FixedTime>,
 ) {
-    coffee_spawn_timer.timer.tick(time.delta());
-
-    if coffee_spawn_timer.timer.just_finished() {
-        let window = window_query.get_single().unwrap();
-        let width = (window.width() / 2.0) - (ENEMY_SIZE / 2.0);
-        let height = (window.height() / 2.0) - (ENEMY_SIZE / 2.0);
+    for power_up in &power_ups.power_ups {
+        let Some(timer) = power_up_timers.timers.get_mut(&power_up.name) else {
+            continue;
+        };
+        timer.tick(fixed_time.period);
 
-        let random_x = (random::<f32>() * width * 2.0) - width;
-        let random_y = (random::<f32>() * height * 2.0) - height;
+        let waiting = pickup_query
+            .iter()
+            .any(|pickup| pickup.name == power_up.name);
+        if !timer.finished() || waiting || active_power_ups.is_active(&power_up.name) {
+            continue;
+        }
 
+        let position = random_arena_position(&arena, &mut game_rng);
         commands.spawn((
             SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
-                texture: asset_server.load("sprites/coffee.png"),
+                transform: Transform::from_translation(position.extend(0.0)),
+                texture: asset_server.load(power_up.sprite.as_str()),
                 ..default()
             },
-            Coffee { collected: false },
+            PowerUpPickup {
+                name: power_up.name.clone(),
+            },
         ));
 
-        let random_time = coffee_spawn_timer.rng.gen_range(0..90);
-        coffee_spawn_timer
-            .timer
-            .set_duration(Duration::from_secs(random_time));
-        coffee_spawn_timer.timer.reset();
+        let delay = power_up_delay(power_up, &wave.definition, &mut game_rng);
+        *timer = Timer::new(delay, TimerMode::Once);
     }
 }
 
-pub fn collect_coffee(
+pub fn collect_power_ups(
     mut commands: Commands,
-    mut coffee_query: Query<(Entity, &Transform, &mut Coffee), With<Coffee>>,
-    player_query: Query<&Transform, With<Player>>,
-    asset_server: Res<AssetServer>,
-    mut music_controller: Query<&AudioSink, With<MrProducerSong>>,
-    mut mr_producer_timer: ResMut<MrProducerTimer>,
+    power_ups: Res<PowerUps>,
+    pickup_query: Query<(Entity, &Transform, &PowerUpPickup)>,
+    player_query: Query<(&Transform, &Interpolated), With<Player>>,
+    mut active_power_ups: ResMut<ActivePowerUps>,
+    mut power_up_event_writer: EventWriter<PowerUpCollected>,
+    mut run_stats: ResMut<RunStats>,
 ) {
-    if let Ok(player_transform) = player_query.get_single() {
-        for (coffee_entity, coffee_transform, mut coffee) in coffee_query.iter_mut() {
-            if is_collision(
-                coffee_transform.translation.x,
-                coffee_transform.translation.y,
-                player_transform.translation.x,
-                player_transform.translation.y,
+    if let Ok((player_transform, interpolated)) = player_query.get_single() {
+        for (pickup_entity, pickup_transform, pickup) in pickup_query.iter() {
+            if is_collision_along(
+                pickup_transform.translation.truncate(),
+                interpolated.previous.truncate(),
+                player_transform.translation.truncate(),
             ) {
-                for mr_producer_controller in music_controller.iter_mut() {
-                    mr_producer_controller.stop();
-                }
-                mr_producer_timer.timer.set_duration(Duration::from_secs(6));
-                mr_producer_timer.timer.set_elapsed(Duration::from_secs(0));
-
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("audio/tatebass.ogg"),
-                    ..default()
+                let Some(power_up) = power_ups.get(&pickup.name) else {
+                    continue;
+                };
+                commands.entity(pickup_entity).despawn();
+                active_power_ups.activate(power_up);
+                run_stats.count_power_up(&pickup.name);
+                power_up_event_writer.send(PowerUpCollected {
+                    name: pickup.name.clone(),
+                    effect: power_up.effect,
                 });
-
-                coffee.collected = true;
-                commands.entity(coffee_entity).despawn();
             }
         }
     }
 }
 
-pub fn spawn_invincibility(
-    mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    asset_server: Res<AssetServer>,
-    mut invinci_spawn_timer: ResMut<SpawnInvinciTimer>,
-    mut invinci_query: Query<Entity, With<Invinci>>,
-    invinci_state: Res<State<Invincible>>,
-    time: Res<Time>,
+/// Pulls money within reach of an active magnet toward the player, faster
+/// the closer it gets.
+pub fn attract_money(
+    active_power_ups: Res<ActivePowerUps>,
+    player_query: Query<&Transform, With<Player>>,
+    mut star_query: Query<&mut Transform, (With<Star>, Without<Player>)>,
+    fixed_time: Res<FixedTime>,
 ) {
-    invinci_spawn_timer.timer.tick(time.delta());
-
-    let mut invinci_exist: bool = false;
-    for _invinci_entity in invinci_query.iter_mut() {
-        invinci_exist = true;
-    }
-
-    if invinci_spawn_timer.timer.finished() && !invinci_exist && *invinci_state != Invincible::On {
-        let window = window_query.get_single().unwrap();
-        let width = (window.width() / 2.0) - (ENEMY_SIZE / 2.0);
-        let height = (window.height() / 2.0) - (ENEMY_SIZE / 2.0);
-
-        let random_x = (random::<f32>() * width * 2.0) - width;
-        let random_y = (random::<f32>() * height * 2.0) - height;
-
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
-                texture: asset_server.load("sprites/cigars.png"),
-                ..default()
-            },
-            Invinci {},
-        ));
+    let Some(radius) = active_power_ups.magnet_radius() else {
+        return;
+    };
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player = player_transform.translation.truncate();
 
-        let random_time = invinci_spawn_timer.rng.gen_range(0..240);
-        invinci_spawn_timer
-            .timer
-            .set_duration(Duration::from_secs(random_time));
-        invinci_spawn_timer.timer.reset();
+    for mut star_transform in star_query.iter_mut() {
+        let offset = player - star_transform.translation.truncate();
+        let distance = offset.length();
+        if distance >= radius || distance == 0.0 {
+            continue;
+        }
+        let closeness = 1.0 - distance / radius;
+        let speed =
+            MAGNET_MIN_SPEED + (MAGNET_MAX_SPEED - MAGNET_MIN_SPEED) * closeness * closeness;
+        let step = (speed * fixed_time.period.as_secs_f32()).min(distance);
+        star_transform.translation += (offset / distance * step).extend(0.0);
     }
 }
 
-pub fn collect_invincibility(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut invinci_state: ResMut<NextState<Invincible>>,
-    state: Res<State<Invincible>>,
-    mut invinci_query: Query<(Entity, &Transform), With<Invinci>>,
-    player_query: Query<&Transform, With<Player>>,
-    mut music_controller: Query<&AudioSink, With<MrProducerSong>>,
-    mut mr_producer_timer: ResMut<MrProducerTimer>,
-    mut invinci_duration_timer: ResMut<InvinciDurationTimer>,
-    time: Res<Time>,
+pub fn tick_power_ups(
+    mut active_power_ups: ResMut<ActivePowerUps>,
+    mut run_stats: ResMut<RunStats>,
+    fixed_time: Res<FixedTime>,
 ) {
-    match *state.get() {
-        Invincible::On => {
-            invinci_duration_timer.timer.tick(time.delta());
-            if invinci_duration_timer.timer.just_finished() {
-                invinci_state.set(Invincible::Off)
-            }
-        }
-        Invincible::Off => {
-            if let Ok(player_transform) = player_query.get_single() {
-                for (invinci_entity, invinci_transform) in invinci_query.iter_mut() {
-                    if is_collision(
-                        invinci_transform.translation.x,
-                        invinci_transform.translation.y,
-                        player_transform.translation.x,
-                        player_transform.translation.y,
-                    ) {
-                        invinci_state.set(Invincible::On);
-                        commands.entity(invinci_entity).despawn();
-
-                        for mr_producer_controller in music_controller.iter_mut() {
-                            mr_producer_controller.stop();
-                        }
-                        mr_producer_timer
-                            .timer
-                            .set_duration(Duration::from_secs(32));
-                        mr_producer_timer.timer.set_elapsed(Duration::from_secs(0));
-
-                        commands.spawn((
-                            AudioBundle {
-                                source: asset_server.load("audio/Invincibility.oga"),
-                                ..default()
-                            },
-                            InvinciSong {},
-                        ));
-                    }
-                }
-            }
-        }
+    if active_power_ups.invincible() {
+        run_stats.invincible_secs += fixed_time.period.as_secs_f32();
     }
+    active_power_ups.tick(fixed_time.period);
 }
 
 pub fn collect_stars(
     mut commands: Commands,
     mut star_query: Query<(Entity, &Transform), With<Star>>,
-    player_query: Query<&Transform, With<Player>>,
-    asset_server: Res<AssetServer>,
+    player_query: Query<(&Transform, &Interpolated), With<Player>>,
+    mut star_event_writer: EventWriter<StarCollected>,
     mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+    mut run_stats: ResMut<RunStats>,
 ) {
-    if let Ok(player_transform) = player_query.get_single() {
+    if let Ok((player_transform, interpolated)) = player_query.get_single() {
         for (star_entity, star_transform) in star_query.iter_mut() {
-            if is_collision(
-                star_transform.translation.x,
-                star_transform.translation.y,
-                player_transform.translation.x,
-                player_transform.translation.y,
+            if is_collision_along(
+                star_transform.translation.truncate(),
+                interpolated.previous.truncate(),
+                player_transform.translation.truncate(),
             ) {
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("audio/cha.ogg"),
-                    ..default()
+                let points = combo.collect();
+                star_event_writer.send(StarCollected {
+                    position: star_transform.translation.truncate(),
+                    points,
+                    multiplier: combo.multiplier,
                 });
                 commands.entity(star_entity).despawn();
 
-                score.value += 1;
+                score.value += points;
+                run_stats.money += 1;
             }
         }
     }
 }
 
-pub fn player_movement(
+/// Presses and releases each `Action` to match the keys bound to it and the
+/// buttons of every connected gamepad.
+pub fn update_actions(
     keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<&mut Transform, With<Player>>,
-    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    bindings: Res<Bindings>,
+    mut actions: ResMut<Input<Action>>,
 ) {
-    if let Ok(mut transform) = player_query.get_single_mut() {
-        let mut direction = Vec3::ZERO;
-
-        if keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A) {
-            direction += Vec3::new(-1.0, 0.0, 0.0)
-        }
-        if keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D) {
-            direction += Vec3::new(1.0, 0.0, 0.0)
+    actions.clear();
+    for action in Action::ALL {
+        let keys = bindings.keys(action);
+        let buttons: Vec<GamepadButton> = gamepads
+            .iter()
+            .flat_map(|gamepad| {
+                action
+                    .gamepad_buttons()
+                    .iter()
+                    .map(move |button_type| GamepadButton::new(gamepad, *button_type))
+            })
+            .collect();
+        if keyboard_input.any_pressed(keys.iter().copied())
+            || keyboard_input.any_just_pressed(keys.iter().copied())
+            || gamepad_buttons.any_pressed(buttons.iter().copied())
+            || gamepad_buttons.any_just_pressed(buttons)
+        {
+            actions.press(action);
+        } else {
+            actions.release(action);
         }
-        if keyboard_input.pressed(KeyCode::Up) || keyboard_input.pressed(KeyCode::W) {
-            direction += Vec3::new(0.0, 1.0, 0.0)
+    }
+}
+
+/// Holds on to a dash press until the next gameplay tick reads it.
+pub fn request_dash(actions: Res<Input<Action>>, mut dash_requested: ResMut<DashRequested>) {
+    if actions.just_pressed(Action::Dash) {
+        dash_requested.value = true;
+    }
+}
+
+/// Digital directions come from `Input<Action>`; a tilted left stick takes
+/// over when it is pushed further than that, moving the player slower than
+/// `PLAYER_SPEED` when only partly tilted. In `ControlMode::Mouse` the player
+/// heads for `MouseTarget` instead, slowing down so it stops on the spot.
+pub fn read_player_input(
+    actions: Res<Input<Action>>,
+    gamepads: Res<Gamepads>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    control_mode: Res<ControlMode>,
+    mouse_target: Res<MouseTarget>,
+    fixed_time: Res<FixedTime>,
+    player_query: Query<&Transform, With<Player>>,
+    mut player_input: ResMut<PlayerInput>,
+    mut dash_requested: ResMut<DashRequested>,
+) {
+    let dash = dash_requested.value;
+    dash_requested.value = false;
+
+    if *control_mode == ControlMode::Mouse {
+        let direction = match (mouse_target.position, player_query.get_single()) {
+            (Some(target), Ok(transform)) => {
+                let max_step = PLAYER_SPEED * fixed_time.period.as_secs_f32();
+                (target - transform.translation.truncate()) / max_step
+            }
+            _ => Vec2::ZERO,
+        };
+        *player_input = PlayerInput {
+            dash,
+            ..PlayerInput::from_direction(direction)
+        };
+        return;
+    }
+
+    let mut direction = Vec2::ZERO;
+
+    if actions.pressed(Action::MoveLeft) {
+        direction += Vec2::new(-1.0, 0.0)
+    }
+    if actions.pressed(Action::MoveRight) {
+        direction += Vec2::new(1.0, 0.0)
+    }
+    if actions.pressed(Action::MoveUp) {
+        direction += Vec2::new(0.0, 1.0)
+    }
+    if actions.pressed(Action::MoveDown) {
+        direction += Vec2::new(0.0, -1.0)
+    }
+
+    if direction.length() > 0.0 {
+        direction = direction.normalize()
+    }
+
+    for gamepad in gamepads.iter() {
+        let stick = Vec2::new(
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or(0.0),
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+                .unwrap_or(0.0),
+        );
+        let stick_direction = stick_direction(stick);
+        if stick_direction.length() > direction.length() {
+            direction = stick_direction;
         }
-        if keyboard_input.pressed(KeyCode::Down) || keyboard_input.pressed(KeyCode::S) {
-            direction += Vec3::new(0.0, -1.0, 0.0)
+    }
+
+    *player_input = PlayerInput {
+        dash,
+        ..PlayerInput::from_direction(direction)
+    };
+}
+
+/// Adds a shield for every shield power-up collected, up to its cap.
+pub fn grant_shields(
+    mut reader: EventReader<PowerUpCollected>,
+    mut player_query: Query<&mut Shields, With<Player>>,
+) {
+    for power_up_collected in reader.iter() {
+        if let PowerUpEffect::Shield { cap } = power_up_collected.effect {
+            if let Ok(mut shields) = player_query.get_single_mut() {
+                shields.count = (shields.count + 1).min(cap);
+            }
         }
+    }
+}
 
-        if direction.length() > 0.0 {
-            direction = direction.normalize()
+/// Runs the dash clocks and starts a dash when one is asked for, as long as
+/// the player is moving and the last one has cooled down.
+pub fn update_dash(
+    player_input: Res<PlayerInput>,
+    mut player_query: Query<&mut Dash, With<Player>>,
+    fixed_time: Res<FixedTime>,
+) {
+    if let Ok(mut dash) = player_query.get_single_mut() {
+        dash.timer.tick(fixed_time.period);
+        dash.cooldown.tick(fixed_time.period);
+
+        let direction = player_input.direction();
+        if player_input.dash && dash.cooldown.finished() && direction != Vec3::ZERO {
+            dash.velocity = direction.normalize() * DASH_SPEED;
+            dash.timer.reset();
+            dash.cooldown.reset();
         }
+    }
+}
 
-        transform.translation += direction * PLAYER_SPEED * time.delta_seconds();
+pub fn player_movement(
+    player_input: Res<PlayerInput>,
+    mut player_query: Query<(&mut Transform, &Dash), With<Player>>,
+    fixed_time: Res<FixedTime>,
+) {
+    if let Ok((mut transform, dash)) = player_query.get_single_mut() {
+        let velocity = if dash.is_dashing() {
+            dash.velocity
+        } else {
+            player_input.direction() * PLAYER_SPEED
+        };
+        transform.translation += velocity * fixed_time.period.as_secs_f32();
     }
 }
 
-pub fn enemy_movement(mut enemy_query: Query<(&mut Transform, &mut Enemy)>, time: Res<Time>) {
-    for (mut transform, enemy) in enemy_query.iter_mut() {
+pub fn enemy_movement(
+    mut enemy_query: Query<(&mut Transform, &mut Enemy, Option<&EnemyKind>)>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    wave: Res<Wave>,
+    fixed_time: Res<FixedTime>,
+    enemy_time_scale: Res<EnemyTimeScale>,
+) {
+    let delta = fixed_time.period.as_secs_f32() * enemy_time_scale.value;
+    let player_transform = player_query.get_single().ok();
+
+    for (mut transform, mut enemy, kind) in enemy_query.iter_mut() {
+        let mut speed = ENEMY_SPEED * wave.definition.speed;
+
+        if kind == Some(&EnemyKind::Chaser) {
+            speed = speed.min(CHASER_MAX_SPEED);
+            if let Some(player_transform) = player_transform {
+                let to_player = (player_transform.translation - transform.translation).truncate();
+                if to_player != Vec2::ZERO {
+                    let max_turn = CHASER_TURN_RATE * delta;
+                    let turn = enemy
+                        .direction
+                        .angle_between(to_player)
+                        .clamp(-max_turn, max_turn);
+                    enemy.direction = Vec2::from_angle(turn).rotate(enemy.direction);
+                }
+            }
+        }
+
         let direction = Vec3::new(enemy.direction.x, enemy.direction.y, 0.0);
-        transform.translation += direction * ENEMY_SPEED * time.delta_seconds();
+        transform.translation += direction * speed * delta;
+    }
+}
+
+/// Bounces agents off each other as equal-mass elastic circles, using the
+/// same radius as `is_collision`. Agents travel at a fixed speed, so only the
+/// direction changes hands.
+pub fn collide_enemies(mut enemy_query: Query<(&mut Transform, &mut Enemy)>) {
+    let mut combinations = enemy_query.iter_combinations_mut();
+    while let Some([(mut transform_a, mut enemy_a), (mut transform_b, mut enemy_b)]) =
+        combinations.fetch_next()
+    {
+        let a = transform_a.translation;
+        let b = transform_b.translation;
+        if !is_collision(a.x, a.y, b.x, b.y) {
+            continue;
+        }
+
+        let offset = (b - a).truncate();
+        let normal = offset.try_normalize().unwrap_or(Vec2::X);
+
+        let overlap = (PLAYER_SIZE - offset.length()) / 2.0;
+        transform_a.translation -= (normal * overlap).extend(0.0);
+        transform_b.translation += (normal * overlap).extend(0.0);
+
+        let approach = enemy_a.direction.dot(normal) - enemy_b.direction.dot(normal);
+        if approach <= 0.0 {
+            continue;
+        }
+        enemy_a.direction = (enemy_a.direction - normal * approach)
+            .try_normalize()
+            .unwrap_or(-normal);
+        enemy_b.direction = (enemy_b.direction + normal * approach)
+            .try_normalize()
+            .unwrap_or(normal);
     }
 }
 
 pub fn confine_enemy_to_window(
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     mut enemy_query: Query<(&mut Transform, &mut Enemy)>,
 ) {
-    let window = window_query.get_single().unwrap();
-    let half_window_width = window.width() / 2.0;
-    let half_window_height = window.height() / 2.0;
+    let half_window_width = arena.width / 2.0;
+    let half_window_height = arena.height / 2.0;
     let half_enemy_size = ENEMY_SIZE / 2.0;
 
     for (mut transform, mut enemy) in enemy_query.iter_mut() {
@@ -515,43 +817,166 @@ pub fn confine_enemy_to_window(
 pub fn detect_collision(
     mut commands: Commands,
     mut game_over_event_writer: EventWriter<GameOver>,
-    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut player_hit_event_writer: EventWriter<PlayerHit>,
+    mut shield_event_writer: EventWriter<ShieldAbsorbed>,
+    mut player_query: Query<
+        (Entity, &Transform, &mut Lives, &mut Shields, &Dash),
+        (With<Player>, Without<Recovering>),
+    >,
     enemy_query: Query<(Entity, &Transform), With<Enemy>>,
-    asset_server: Res<AssetServer>,
-    invinci_state: Res<State<Invincible>>,
+    active_power_ups: Res<ActivePowerUps>,
     score: Res<Score>,
+    wave: Res<Wave>,
+    survival_time: Res<SurvivalTime>,
+    mut run_stats: ResMut<RunStats>,
+    game_rng: Res<GameRng>,
 ) {
-    if let Ok((player_entity, player_transform)) = player_query.get_single() {
-        for (_enemy_entity, enemy_transform) in enemy_query.iter() {
+    if active_power_ups.invincible() {
+        return;
+    }
+
+    if let Ok((player_entity, player_transform, mut lives, mut shields, dash)) =
+        player_query.get_single_mut()
+    {
+        if dash.is_dashing() {
+            return;
+        }
+        for (enemy_entity, enemy_transform) in enemy_query.iter() {
             let player_x = player_transform.translation.x;
             let player_y = player_transform.translation.y;
             let enemy_x = enemy_transform.translation.x;
             let enemy_y = enemy_transform.translation.y;
 
-            if is_collision(enemy_x, enemy_y, player_x, player_y)
-                && *invinci_state.get() == Invincible::Off
-            {
-                commands.spawn(AudioBundle {
-                    source: asset_server.load("audio/explosionCrunch_000.ogg"),
-                    ..default()
-                });
-                commands.entity(player_entity).despawn();
-                game_over_event_writer.send(GameOver { score: score.value });
+            if is_collision(enemy_x, enemy_y, player_x, player_y) {
+                if shields.count > 0 {
+                    shields.count -= 1;
+                    commands.entity(enemy_entity).despawn();
+                    commands.entity(player_entity).insert(Recovering {
+                        timer: Timer::from_seconds(SHIELD_RECOVERY_SECONDS, TimerMode::Once),
+                    });
+                    shield_event_writer.send(ShieldAbsorbed {
+                        position: enemy_transform.translation.truncate(),
+                        shields: shields.count,
+                    });
+                    break;
+                }
+
+                lives.value = lives.value.saturating_sub(1);
+                run_stats.hits += 1;
+                player_hit_event_writer.send(PlayerHit { lives: lives.value });
+
+                if lives.value == 0 {
+                    commands.entity(player_entity).despawn();
+                    game_over_event_writer.send(GameOver {
+                        score: score.value,
+                        wave: wave.number,
+                        survival_secs: survival_time.value.as_secs_f32(),
+                        agents: enemy_query.iter().len() as u32,
+                        money: run_stats.money,
+                        power_ups: run_stats.power_ups.clone(),
+                        seed: game_rng.seed,
+                    });
+                } else {
+                    let away = (player_transform.translation - enemy_transform.translation)
+                        .try_normalize()
+                        .unwrap_or(Vec3::Y);
+                    commands.entity(player_entity).insert((
+                        Recovering {
+                            timer: Timer::from_seconds(RECOVERY_SECONDS, TimerMode::Once),
+                        },
+                        Knockback {
+                            velocity: away * KNOCKBACK_SPEED,
+                        },
+                    ));
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// Adds up how far the player moved this tick, knockback included.
+pub fn track_distance(
+    player_query: Query<(&Transform, &Interpolated), With<Player>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    if let Ok((transform, interpolated)) = player_query.get_single() {
+        run_stats.distance += (transform.translation - interpolated.previous).length();
+    }
+}
+
+/// Marks agents that come within `NEAR_MISS_DISTANCE` and counts a near miss
+/// when one leaves again. Agents leaving while the player is recovering or
+/// invincible don't count, so the hit that caused it never shows up twice.
+pub fn count_near_misses(
+    mut commands: Commands,
+    player_query: Query<(&Transform, &Dash, Option<&Recovering>), With<Player>>,
+    enemy_query: Query<(Entity, &Transform, Option<&NearMiss>), With<Enemy>>,
+    active_power_ups: Res<ActivePowerUps>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    let Ok((player_transform, dash, recovering)) = player_query.get_single() else {
+        return;
+    };
+    let safe = recovering.is_some() || dash.is_dashing() || active_power_ups.invincible();
+
+    for (enemy_entity, enemy_transform, near_miss) in enemy_query.iter() {
+        let close = player_transform
+            .translation
+            .truncate()
+            .distance(enemy_transform.translation.truncate())
+            < NEAR_MISS_DISTANCE;
+        match (close, near_miss.is_some()) {
+            (true, false) => {
+                commands.entity(enemy_entity).insert(NearMiss {});
+            }
+            (false, true) => {
+                commands.entity(enemy_entity).remove::<NearMiss>();
+                if !safe {
+                    run_stats.near_misses += 1;
+                }
             }
+            _ => {}
+        }
+    }
+}
+
+pub fn tick_recovery(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut Recovering)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (player_entity, mut recovering) in player_query.iter_mut() {
+        recovering.timer.tick(fixed_time.period);
+        if recovering.timer.finished() {
+            commands.entity(player_entity).remove::<Recovering>();
+        }
+    }
+}
+
+pub fn apply_knockback(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut Transform, &mut Knockback)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (player_entity, mut transform, mut knockback) in player_query.iter_mut() {
+        transform.translation += knockback.velocity * fixed_time.period.as_secs_f32();
+        knockback.velocity *= KNOCKBACK_DECAY;
+        if knockback.velocity.length() < PLAYER_SPEED / 10.0 {
+            commands.entity(player_entity).remove::<Knockback>();
         }
     }
 }
 
 pub fn window_border_movement(
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     mut player_query: Query<&mut Transform, With<Player>>,
 ) {
     if let Ok(mut transform) = player_query.get_single_mut() {
         let player_x = transform.translation.x;
         let player_y = transform.translation.y;
-        let window = window_query.get_single().unwrap();
-        let half_window_width = window.width() / 2.0;
-        let half_window_height = window.height() / 2.0;
+        let half_window_width = arena.width / 2.0;
+        let half_window_height = arena.height / 2.0;
         let half_player_size = PLAYER_SIZE / 2.0;
 
         if player_x + half_player_size > half_window_width {
@@ -569,75 +994,451 @@ pub fn window_border_movement(
     }
 }
 
+pub fn tick_combo(mut combo: ResMut<Combo>, fixed_time: Res<FixedTime>) {
+    combo.tick(fixed_time.period);
+}
+
 pub fn update_score(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     score: Res<Score>,
+    combo: Res<Combo>,
     score_component_query: Query<Entity, With<ScoreComponent>>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
 ) {
     for score_component_entity in score_component_query.iter() {
         commands.entity(score_component_entity).despawn();
     }
 
-    let window = window_query.get_single().unwrap();
-    let x = -window.width() / 2.0 + 59.0;
-    let y = window.height() / 2.0 - 30.0;
+    let x = -arena.width / 2.0 + 59.0;
+    let y = arena.height / 2.0 - 30.0;
 
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     let text_style = TextStyle {
-        font: font,
+        font,
         font_size: 30.0,
         color: Color::WHITE,
     };
+    let text = if combo.multiplier > 1 {
+        format!("Score: ${} x{}", score.value, combo.multiplier)
+    } else {
+        format!("Score: ${}", score.value)
+    };
     commands.spawn((
         Text2dBundle {
-            text: Text::from_section(format!("Score: ${}", score.value), text_style),
-            transform: Transform::from_translation(Vec3::new(x, y, 0.0)),
+            text: Text::from_section(text, text_style).with_alignment(TextAlignment::Left),
+            text_anchor: Anchor::CenterLeft,
+            transform: Transform::from_translation(Vec3::new(x - 49.0, y, 0.0)),
             ..default()
         },
         ScoreComponent {},
     ));
 }
 
-pub fn tick_enemy_timer(mut enemy_timer: ResMut<SpawnEnemyTimer>, time: Res<Time>) {
-    enemy_timer.timer.tick(time.delta());
-}
-
-pub fn pause_game(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut game_state: ResMut<NextState<GameState>>,
-    game_state_const: Res<State<GameState>>,
-    mut music_controller: Query<&AudioSink, With<InvinciSong>>,
-    invinci_state: Res<State<Invincible>>,
+/// An icon and the seconds left for every power-up still running.
+pub fn draw_power_up_timers(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    arena: Res<Arena>,
+    power_ups: Res<PowerUps>,
+    active_power_ups: Res<ActivePowerUps>,
+    display_query: Query<Entity, With<PowerUpTimerDisplay>>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
-        match *game_state_const.get() {
-            GameState::Game => {
-                game_state.set(GameState::Paused);
-
-                match *invinci_state.get() {
-                    Invincible::On => {
-                        for invinci_controller in music_controller.iter_mut() {
-                            invinci_controller.pause();
-                        }
-                    }
-                    Invincible::Off => {}
-                }
-            }
-            GameState::Paused => {
-                game_state.set(GameState::Game);
+    for display_entity in display_query.iter() {
+        commands.entity(display_entity).despawn();
+    }
 
-                match *invinci_state.get() {
-                    Invincible::On => {
-                        for invinci_controller in music_controller.iter_mut() {
-                            invinci_controller.play();
-                        }
-                    }
-                    Invincible::Off => {}
-                }
-            }
-            GameState::Menu => {}
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    for (row, (name, active)) in active_power_ups.active.iter().enumerate() {
+        let Some(power_up) = power_ups.get(name) else {
+            continue;
+        };
+        let x = -arena.width / 2.0 + 26.0;
+        let y = arena.height / 2.0 - 135.0 - row as f32 * 40.0;
+
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform::from_xyz(x, y, 0.0).with_scale(Vec3::splat(0.5)),
+                texture: asset_server.load(power_up.sprite.as_str()),
+                ..default()
+            },
+            PowerUpTimerDisplay {},
+        ));
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("{}s", active.timer.remaining_secs().ceil() as u32),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 26.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                text_anchor: Anchor::CenterLeft,
+                transform: Transform::from_xyz(x + 24.0, y, 0.0),
+                ..default()
+            },
+            PowerUpTimerDisplay {},
+        ));
+    }
+}
+
+/// A ring around the player that fills up as the dash cools down.
+pub fn draw_dash_cooldown(
+    mut gizmos: Gizmos,
+    player_query: Query<(&Transform, &Dash), With<Player>>,
+) {
+    if let Ok((transform, dash)) = player_query.get_single() {
+        if dash.cooldown.finished() {
+            return;
+        }
+        let position = transform.translation.truncate();
+        let radius = PLAYER_SIZE * 0.7;
+        gizmos.circle_2d(position, radius, Color::rgba(1.0, 1.0, 1.0, 0.2));
+        gizmos.arc_2d(
+            position,
+            0.0,
+            std::f32::consts::TAU * dash.cooldown.percent(),
+            radius,
+            SELECTED_TEXT_COLOR,
+        );
+    }
+}
+
+/// One bubble around the player per shield.
+pub fn draw_shield_bubbles(
+    mut gizmos: Gizmos,
+    player_query: Query<(&Transform, &Shields), With<Player>>,
+) {
+    if let Ok((transform, shields)) = player_query.get_single() {
+        for shield in 0..shields.count {
+            gizmos.circle_2d(
+                transform.translation.truncate(),
+                PLAYER_SIZE * 0.8 + shield as f32 * 6.0,
+                SHIELD_COLOR,
+            );
+        }
+    }
+}
+
+/// A shield icon per shield, after the hearts.
+pub fn draw_shields(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    arena: Res<Arena>,
+    player_query: Query<(&Lives, &Shields), With<Player>>,
+    shield_display_query: Query<Entity, With<ShieldDisplay>>,
+) {
+    for shield_display_entity in shield_display_query.iter() {
+        commands.entity(shield_display_entity).despawn();
+    }
+
+    if let Ok((lives, shields)) = player_query.get_single() {
+        for shield in 0..shields.count {
+            let x = -arena.width / 2.0 + 26.0 + (lives.value + shield) as f32 * 36.0;
+            let y = arena.height / 2.0 - 70.0;
+
+            commands.spawn((
+                SpriteBundle {
+                    transform: Transform::from_xyz(x, y, 0.0).with_scale(Vec3::splat(0.5)),
+                    texture: asset_server.load("sprites/shield.png"),
+                    ..default()
+                },
+                ShieldDisplay {},
+            ));
+        }
+    }
+}
+
+pub fn spawn_shield_bursts(mut commands: Commands, mut reader: EventReader<ShieldAbsorbed>) {
+    for shield_absorbed in reader.iter() {
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(
+                shield_absorbed.position.extend(0.0),
+            )),
+            ShieldBurst {
+                timer: Timer::from_seconds(SHIELD_BURST_SECONDS, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+/// Grows each shield burst into a fading ring, then clears it away.
+pub fn animate_shield_bursts(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    mut burst_query: Query<(Entity, &Transform, &mut ShieldBurst)>,
+) {
+    for (burst_entity, transform, mut burst) in burst_query.iter_mut() {
+        burst.timer.tick(time.delta());
+        if burst.timer.finished() {
+            commands.entity(burst_entity).despawn();
+            continue;
+        }
+        let progress = burst.timer.percent();
+        let mut color = SHIELD_COLOR;
+        color.set_a(1.0 - progress);
+        gizmos.circle_2d(
+            transform.translation.truncate(),
+            PLAYER_SIZE * (0.5 + 1.5 * progress),
+            color,
+        );
+    }
+}
+
+/// A bar under the hearts showing how long is left to keep the combo going.
+pub fn draw_combo_meter(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    combo: Res<Combo>,
+    combo_meter_query: Query<Entity, With<ComboMeter>>,
+) {
+    for combo_meter_entity in combo_meter_query.iter() {
+        commands.entity(combo_meter_entity).despawn();
+    }
+    if combo.timer.finished() {
+        return;
+    }
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: SELECTED_TEXT_COLOR,
+                custom_size: Some(Vec2::new(
+                    COMBO_METER_WIDTH * combo.timer.percent_left(),
+                    8.0,
+                )),
+                anchor: Anchor::CenterLeft,
+                ..default()
+            },
+            transform: Transform::from_xyz(
+                -arena.width / 2.0 + 10.0,
+                arena.height / 2.0 - 100.0,
+                0.0,
+            ),
+            ..default()
+        },
+        ComboMeter {},
+    ));
+}
+
+pub fn spawn_score_popups(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut reader: EventReader<StarCollected>,
+) {
+    for star_collected in reader.iter() {
+        let text_style = TextStyle {
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 24.0 + star_collected.multiplier as f32 * 4.0,
+            color: SELECTED_TEXT_COLOR,
+        };
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(format!("+${}", star_collected.points), text_style),
+                transform: Transform::from_translation(star_collected.position.extend(2.0)),
+                ..default()
+            },
+            ScorePopup {
+                timer: Timer::from_seconds(SCORE_POPUP_SECONDS, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+/// Floats score popups upward, fading them out before they go.
+pub fn animate_score_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popup_query: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+) {
+    for (popup_entity, mut popup, mut transform, mut text) in popup_query.iter_mut() {
+        popup.timer.tick(time.delta());
+        if popup.timer.finished() {
+            commands.entity(popup_entity).despawn();
+            continue;
+        }
+        transform.translation.y += SCORE_POPUP_SPEED * time.delta_seconds();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(popup.timer.percent_left());
+        }
+    }
+}
+
+pub fn draw_lives(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    arena: Res<Arena>,
+    player_query: Query<&Lives, With<Player>>,
+    lives_query: Query<Entity, With<LivesDisplay>>,
+) {
+    for lives_entity in lives_query.iter() {
+        commands.entity(lives_entity).despawn();
+    }
+
+    let lives = match player_query.get_single() {
+        Ok(lives) => lives.value,
+        Err(_) => 0,
+    };
+
+    for heart in 0..lives {
+        let x = -arena.width / 2.0 + 26.0 + heart as f32 * 36.0;
+        let y = arena.height / 2.0 - 70.0;
+
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform::from_xyz(x, y, 0.0),
+                texture: asset_server.load("sprites/heart.png"),
+                ..default()
+            },
+            LivesDisplay {},
+        ));
+    }
+}
+
+pub fn draw_wave_banner(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    wave: Res<Wave>,
+    game_state: Res<State<GameState>>,
+    banner_query: Query<Entity, With<WaveBanner>>,
+) {
+    for banner_entity in banner_query.iter() {
+        commands.entity(banner_entity).despawn();
+    }
+
+    let number = match wave.phase {
+        WavePhase::Intermission => wave.number + 1,
+        WavePhase::Active if wave.timer.elapsed_secs() < WAVE_BANNER_SECONDS => wave.number,
+        WavePhase::Active => return,
+    };
+    if *game_state.get() != GameState::Game {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = TextStyle {
+        font,
+        font_size: 80.0,
+        color: Color::WHITE,
+    };
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(format!("Wave {}", number), text_style),
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            ..default()
+        },
+        WaveBanner {},
+    ));
+}
+
+pub fn tick_enemy_timer(
+    mut enemy_timer: ResMut<SpawnEnemyTimer>,
+    fixed_time: Res<FixedTime>,
+    enemy_time_scale: Res<EnemyTimeScale>,
+) {
+    enemy_timer
+        .timer
+        .tick(fixed_time.period.mul_f32(enemy_time_scale.value));
+}
+
+/// Slows agents down while a time-slow power-up is running. Anything left
+/// running when the run ends stops counting on the menus.
+pub fn update_enemy_time_scale(
+    active_power_ups: Res<ActivePowerUps>,
+    game_state: Res<State<GameState>>,
+    mut enemy_time_scale: ResMut<EnemyTimeScale>,
+) {
+    let value = match game_state.get() {
+        GameState::Game | GameState::Paused => active_power_ups.enemy_time_scale(),
+        _ => 1.0,
+    };
+    enemy_time_scale.set_if_neq(EnemyTimeScale { value });
+}
+
+/// Greys out the arena and the agents when time starts being slowed, and
+/// brings the colour back when it stops.
+pub fn draw_time_slow_tint(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    enemy_time_scale: Res<EnemyTimeScale>,
+    tint_query: Query<Entity, With<TimeSlowTint>>,
+    mut enemy_query: Query<(&mut Sprite, &EnemyKind)>,
+) {
+    for (mut sprite, kind) in enemy_query.iter_mut() {
+        sprite.color = agent_color(kind, &enemy_time_scale);
+    }
+
+    match (enemy_time_scale.value < 1.0, tint_query.get_single()) {
+        (true, Err(_)) => {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(0.5, 0.5, 0.55, 0.3),
+                        custom_size: Some(Vec2::new(arena.width, arena.height)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, 0.5),
+                    ..default()
+                },
+                TimeSlowTint {},
+            ));
+        }
+        (false, Ok(tint_entity)) => {
+            commands.entity(tint_entity).despawn();
+        }
+        _ => {}
+    }
+}
+
+/// Greys out agents that turn up while time is already slowed.
+pub fn tint_new_agents(
+    enemy_time_scale: Res<EnemyTimeScale>,
+    mut enemy_query: Query<(&mut Sprite, &EnemyKind), Added<EnemyKind>>,
+) {
+    for (mut sprite, kind) in enemy_query.iter_mut() {
+        sprite.color = agent_color(kind, &enemy_time_scale);
+    }
+}
+
+/// Puts simulated entities back where the last tick left them, undoing the
+/// interpolation applied for rendering.
+pub fn restore_simulated_transforms(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        transform.translation = interpolated.current;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+pub fn store_simulated_transforms(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.current = transform.translation;
+    }
+}
+
+/// Draws simulated entities between their last two ticks, by how far the
+/// clock has run into the next one.
+pub fn interpolate_transforms(
+    mut query: Query<(&mut Transform, &Interpolated)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0);
+    for (mut transform, interpolated) in query.iter_mut() {
+        transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
+
+pub fn pause_game(
+    actions: Res<Input<Action>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    game_state_const: Res<State<GameState>>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        match *game_state_const.get() {
+            GameState::Game => game_state.set(GameState::Paused),
+            GameState::Paused => game_state.set(GameState::Game),
+            GameState::Menu | GameState::GameOver => {}
         }
     }
 }
@@ -646,29 +1447,29 @@ pub fn fps_system(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     mut tracker: ResMut<FpsTracker>,
+    mut settings: ResMut<Settings>,
     fps_query: Query<Entity, With<FPS>>,
     time: Res<Time>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    keyboard_input: Res<Input<KeyCode>>,
+    arena: Res<Arena>,
+    actions: Res<Input<Action>>,
 ) {
-    if keyboard_input.just_released(KeyCode::F) {
-        tracker.enabled = !tracker.enabled;
+    if actions.just_pressed(Action::ToggleFps) {
+        settings.show_fps = !settings.show_fps;
     }
 
     for fps_entity in fps_query.iter() {
         commands.entity(fps_entity).despawn();
     }
 
-    if tracker.enabled {
+    if settings.show_fps {
         tracker.update(time);
 
-        let window = window_query.get_single().unwrap();
-        let x = window.width() / 2.0 - 50.0;
-        let y = window.height() / 2.0 - 30.0;
+        let x = arena.width / 2.0 - 50.0;
+        let y = arena.height / 2.0 - 30.0;
 
         let font = asset_server.load("fonts/FiraSans-Bold.ttf");
         let text_style = TextStyle {
-            font: font,
+            font,
             font_size: 30.0,
             color: Color::WHITE,
         };
@@ -691,8 +1492,6 @@ pub fn interact_with_play_button(
     mut game_start_event_writer: EventWriter<GameStart>,
     mut game_state: ResMut<NextState<GameState>>,
     game_state_const: Res<State<GameState>>,
-    mut music_controller: Query<&AudioSink, With<InvinciSong>>,
-    invinci_state: Res<State<Invincible>>,
 ) {
     if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
         match *interaction {
@@ -705,17 +1504,8 @@ pub fn interact_with_play_button(
                     }
                     GameState::Paused => {
                         game_state.set(GameState::Game);
-
-                        match *invinci_state.get() {
-                            Invincible::On => {
-                                for invinci_controller in music_controller.iter_mut() {
-                                    invinci_controller.play();
-                                }
-                            }
-                            Invincible::Off => {}
-                        }
                     }
-                    GameState::Game => {}
+                    GameState::Game | GameState::GameOver => {}
                 }
             }
             Interaction::Hovered => {
@@ -752,9 +1542,7 @@ pub fn interact_with_quit_button(
 }
 
 pub fn interact_with_sound_button(
-    mut mut_mr_producer_state: ResMut<NextState<MrProducerState>>,
-    mr_producer_state: Res<State<MrProducerState>>,
-    mut mr_producer_timer: ResMut<MrProducerTimer>,
+    mut settings: ResMut<Settings>,
     mut button_query: Query<
         (&Interaction, &mut BackgroundColor),
         (Changed<Interaction>, With<SoundButton>),
@@ -764,16 +1552,7 @@ pub fn interact_with_sound_button(
         match *interaction {
             Interaction::Pressed => {
                 *background_color = PRESSED_BUTTON_COLOR.into();
-
-                if *mr_producer_state.get() == MrProducerState::On {
-                    mut_mr_producer_state.set(MrProducerState::Off);
-                } else if *mr_producer_state.get() == MrProducerState::Off {
-                    mut_mr_producer_state.set(MrProducerState::On);
-                    mr_producer_timer
-                        .timer
-                        .set_duration(Duration::from_secs(26));
-                    mr_producer_timer.timer.set_elapsed(Duration::from_secs(25));
-                }
+                settings.music = !settings.music;
             }
             Interaction::Hovered => {
                 *background_color = HOVERED_BUTTON_COLOR.into();
@@ -785,6 +1564,67 @@ pub fn interact_with_sound_button(
     }
 }
 
+/// Turns the background music on or off to match `Settings::music`.
+pub fn sync_music_setting(
+    settings: Res<Settings>,
+    mut mut_mr_producer_state: ResMut<NextState<MrProducerState>>,
+    mr_producer_state: Res<State<MrProducerState>>,
+    mut mr_producer_timer: ResMut<MrProducerTimer>,
+) {
+    match (*mr_producer_state.get(), settings.music) {
+        (MrProducerState::On, false) => mut_mr_producer_state.set(MrProducerState::Off),
+        (MrProducerState::Off, true) => {
+            mut_mr_producer_state.set(MrProducerState::On);
+            mr_producer_timer
+                .timer
+                .set_duration(Duration::from_secs(26));
+            mr_producer_timer.timer.set_elapsed(Duration::from_secs(25));
+        }
+        _ => {}
+    }
+}
+
+/// Applies the volume settings: master to everything started from now on,
+/// music straight away to the tracks already playing.
+pub fn apply_volume_settings(
+    settings: Res<Settings>,
+    mut global_volume: ResMut<GlobalVolume>,
+    music_query: Query<&AudioSink, With<Music>>,
+) {
+    *global_volume = GlobalVolume::new(settings.master_volume);
+    for music_controller in music_query.iter() {
+        music_controller.set_volume(settings.master_volume * settings.music_volume);
+    }
+}
+
+pub fn apply_window_settings(
+    settings: Res<Settings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if let Ok(mut window) = window_query.get_single_mut() {
+        window.mode = window_mode(settings.display_mode);
+        let (width, height) = settings.resolution;
+        window.resolution.set(width as f32, height as f32);
+        window.present_mode = present_mode(settings.vsync);
+    }
+}
+
+pub fn apply_input_settings(
+    settings: Res<Settings>,
+    mut bindings: ResMut<Bindings>,
+    mut control_mode: ResMut<ControlMode>,
+) {
+    *bindings = settings.bindings.clone();
+    *control_mode = settings.control_mode;
+}
+
+pub fn save_settings(settings: Res<Settings>) {
+    if settings.is_added() {
+        return;
+    }
+    save_or_warn(&*settings, Settings::FILE);
+}
+
 pub fn mr_producer(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -792,18 +1632,27 @@ pub fn mr_producer(
     mr_producer_state: Res<State<MrProducerState>>,
     time: Res<Time>,
     mut music_controller: Query<&AudioSink, With<MrProducerSong>>,
-    invinci_state: Res<State<Invincible>>,
+    power_ups: Res<PowerUps>,
+    active_power_ups: Res<ActivePowerUps>,
+    settings: Res<Settings>,
 ) {
+    // Power-ups with their own music keep it playing for as long as they last.
+    let overridden = active_power_ups.active.keys().any(|name| {
+        power_ups
+            .get(name)
+            .is_some_and(|power_up| power_up.music.is_some())
+    });
     match *mr_producer_state.get() {
         MrProducerState::On => {
             mr_producer_timer.timer.tick(time.delta());
-            if mr_producer_timer.timer.finished() && *invinci_state.get() == Invincible::Off {
+            if mr_producer_timer.timer.finished() && !overridden {
                 commands.spawn((
                     AudioBundle {
                         source: asset_server.load("audio/mrprod.ogg"),
-                        ..default()
+                        settings: music_playback(&settings),
                     },
                     MrProducerSong {},
+                    Music {},
                 ));
                 mr_producer_timer
                     .timer
@@ -818,50 +1667,1164 @@ pub fn mr_producer(
     }
 }
 
-pub fn spawn_main_menu(
+pub fn play_star_sound(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    score: Res<Score>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut reader: EventReader<StarCollected>,
+    settings: Res<Settings>,
 ) {
-    build_sound_button(&mut commands, &asset_server, &window_query);
-    build_main_menu(&mut commands, &asset_server, &score, window_query);
+    for star_collected in reader.iter() {
+        commands.spawn(AudioBundle {
+            source: asset_server.load("audio/cha.ogg"),
+            settings: PlaybackSettings {
+                speed: combo_pitch(star_collected.multiplier),
+                ..sfx_playback(&settings)
+            },
+        });
+    }
 }
 
-pub fn despawn_main_menu(
+/// Plays each power-up's music in place of the background track.
+pub fn play_power_up_sounds(
     mut commands: Commands,
-    main_menu_query: Query<Entity, With<MainMenu>>,
-    sound_button_query: Query<Entity, With<SoundButton>>,
+    asset_server: Res<AssetServer>,
+    power_ups: Res<PowerUps>,
+    mut reader: EventReader<PowerUpCollected>,
+    mut music_controller: Query<&AudioSink, With<MrProducerSong>>,
+    mut mr_producer_timer: ResMut<MrProducerTimer>,
+    settings: Res<Settings>,
 ) {
-    if let Ok(main_menu_entity) = main_menu_query.get_single() {
-        commands.entity(main_menu_entity).despawn_recursive();
+    for power_up_collected in reader.iter() {
+        let Some(power_up) = power_ups.get(&power_up_collected.name) else {
+            continue;
+        };
+        if let Some(music) = &power_up.music {
+            for mr_producer_controller in music_controller.iter_mut() {
+                mr_producer_controller.stop();
+            }
+            mr_producer_timer
+                .timer
+                .set_duration(Duration::from_secs_f32(music.secs));
+            mr_producer_timer.timer.set_elapsed(Duration::from_secs(0));
+
+            commands.spawn((
+                AudioBundle {
+                    source: asset_server.load(music.track.as_str()),
+                    settings: music_playback(&settings),
+                },
+                PowerUpSong {},
+                Music {},
+            ));
+        }
     }
+}
 
-    if let Ok(sound_button_entity) = sound_button_query.get_single() {
-        commands.entity(sound_button_entity).despawn_recursive();
+/// Drops the pitch of whatever music is playing while time is slowed.
+pub fn slow_music(
+    enemy_time_scale: Res<EnemyTimeScale>,
+    music_query: Query<&AudioSink, With<Music>>,
+) {
+    let speed = if enemy_time_scale.value < 1.0 {
+        TIME_SLOW_MUSIC_SPEED
+    } else {
+        1.0
+    };
+    for music_controller in music_query.iter() {
+        if music_controller.speed() != speed {
+            music_controller.set_speed(speed);
+        }
     }
 }
 
-pub fn fix_menu_first_game(
-    window_query: Query<&Window, With<PrimaryWindow>>,
+pub fn play_hit_sound(
+    mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut reader: EventReader<PlayerHit>,
+    settings: Res<Settings>,
+) {
+    if let Some(_player_hit) = reader.iter().last() {
+        commands.spawn(AudioBundle {
+            source: asset_server.load("audio/explosionCrunch_000.ogg"),
+            settings: sfx_playback(&settings),
+        });
+    }
+}
+
+pub fn play_shield_sound(
     mut commands: Commands,
-    main_menu_query: Query<Entity, With<MainMenu>>,
-    mut sound_button_query: Query<Entity, With<SoundButton>>,
-    score: Res<Score>,
-    mut timer: ResMut<FixMenuTimer>,
-    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut reader: EventReader<ShieldAbsorbed>,
+    settings: Res<Settings>,
 ) {
-    if let Ok(menu_entity) = main_menu_query.get_single() {
-        timer.timer.tick(time.delta());
-        if timer.timer.just_finished() {
-            for sound_button in sound_button_query.iter_mut() {
-                commands.entity(sound_button).despawn();
-                build_sound_button(&mut commands, &asset_server, &window_query);
-            }
+    if let Some(_shield_absorbed) = reader.iter().last() {
+        commands.spawn(AudioBundle {
+            source: asset_server.load("audio/explosionCrunch_000.ogg"),
+            settings: PlaybackSettings {
+                speed: 1.5,
+                ..sfx_playback(&settings)
+            },
+        });
+    }
+}
 
-            commands.entity(menu_entity).despawn();
-            build_main_menu(&mut commands, &asset_server, &score, window_query);
-        }
+pub fn pause_power_up_music(music_controller: Query<&AudioSink, With<PowerUpSong>>) {
+    for power_up_controller in music_controller.iter() {
+        power_up_controller.pause();
+    }
+}
+
+pub fn resume_power_up_music(music_controller: Query<&AudioSink, With<PowerUpSong>>) {
+    for power_up_controller in music_controller.iter() {
+        if !power_up_controller.empty() {
+            power_up_controller.play();
+        }
+    }
+}
+
+pub fn start_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    mut reader: EventReader<GameStart>,
+    game_rng: Res<GameRng>,
+    arena: Res<Arena>,
+) {
+    if let Some(_game_start) = reader.iter().last() {
+        recorder.current = Some(Replay::new(game_rng.seed, &arena));
+    }
+}
+
+pub fn record_input(
+    mut recorder: ResMut<ReplayRecorder>,
+    game_state: Res<State<GameState>>,
+    player_input: Res<PlayerInput>,
+) {
+    if *game_state.get() == GameState::Game {
+        if let Some(replay) = recorder.current.as_mut() {
+            replay.inputs.push(*player_input);
+        }
+    }
+}
+
+pub fn finish_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    mut reader: EventReader<GameOver>,
+    launch_options: Res<LaunchOptions>,
+) {
+    if let Some(game_over) = reader.iter().last() {
+        if let Some(mut replay) = recorder.current.take() {
+            replay.score = game_over.score;
+
+            if let Some(path) = &launch_options.record {
+                match replay.save(path) {
+                    Ok(()) => info!("Saved replay to {}", path.display()),
+                    Err(error) => warn!("Could not save replay to {}: {}", path.display(), error),
+                }
+            }
+            recorder.last = Some(replay);
+        }
+    }
+}
+
+/// Pins the arena to the recorded size so spawns and bounces line up.
+pub fn apply_replay_arena(playback: Res<ReplayPlayback>, mut arena: ResMut<Arena>) {
+    arena.width = playback.replay.arena_width;
+    arena.height = playback.replay.arena_height;
+}
+
+pub fn play_back_input(
+    mut playback: ResMut<ReplayPlayback>,
+    mut player_input: ResMut<PlayerInput>,
+    game_state: Res<State<GameState>>,
+) {
+    if *game_state.get() == GameState::Game {
+        let cursor = playback.cursor;
+        *player_input = match playback.replay.inputs.get(cursor) {
+            Some(input) => *input,
+            None => PlayerInput::default(),
+        };
+        playback.cursor += 1;
+    }
+}
+
+pub fn finish_playback(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    mut reader: EventReader<GameOver>,
+) {
+    if let Some(game_over) = reader.iter().last() {
+        let replay = &playback.replay;
+        if game_over.score == replay.score && playback.cursor == replay.inputs.len() {
+            println!(
+                "Replay verified: ${} after {} ticks",
+                game_over.score, playback.cursor
+            );
+        } else {
+            println!(
+                "Replay diverged: ${} after {} ticks, recorded ${} after {} ticks",
+                game_over.score,
+                playback.cursor,
+                replay.score,
+                replay.inputs.len()
+            );
+        }
+
+        commands.remove_resource::<ReplayPlayback>();
+    }
+}
+
+pub fn spawn_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    score: Res<Score>,
+    arena: Res<Arena>,
+    first_game: Res<FirstGame>,
+    game_rng: Res<GameRng>,
+) {
+    let seed = (!first_game.value).then_some(game_rng.seed);
+    build_sound_button(&mut commands, &asset_server, &arena);
+    build_main_menu(&mut commands, &asset_server, &score, seed, &arena);
+}
+
+pub fn despawn_main_menu(
+    mut commands: Commands,
+    main_menu_query: Query<Entity, With<MainMenu>>,
+    sound_button_query: Query<Entity, With<SoundButton>>,
+) {
+    if let Ok(main_menu_entity) = main_menu_query.get_single() {
+        commands.entity(main_menu_entity).despawn_recursive();
+    }
+
+    if let Ok(sound_button_entity) = sound_button_query.get_single() {
+        commands.entity(sound_button_entity).despawn_recursive();
+    }
+}
+
+pub fn fix_menu_first_game(
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    main_menu_query: Query<Entity, With<MainMenu>>,
+    mut sound_button_query: Query<Entity, With<SoundButton>>,
+    score: Res<Score>,
+    first_game: Res<FirstGame>,
+    game_rng: Res<GameRng>,
+    mut timer: ResMut<FixMenuTimer>,
+    time: Res<Time>,
+) {
+    if let Ok(menu_entity) = main_menu_query.get_single() {
+        timer.timer.tick(time.delta());
+        if timer.timer.just_finished() {
+            for sound_button in sound_button_query.iter_mut() {
+                commands.entity(sound_button).despawn();
+                build_sound_button(&mut commands, &asset_server, &arena);
+            }
+
+            commands.entity(menu_entity).despawn();
+            let seed = (!first_game.value).then_some(game_rng.seed);
+            build_main_menu(&mut commands, &asset_server, &score, seed, &arena);
+        }
+    }
+}
+
+pub fn interact_with_back_button(
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<BackButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                menu_screen.set(MenuScreen::Main);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// Pause leaves any menu page for the main menu, except the name entry,
+/// which has to be finished with Enter, and while a key is being rebound.
+pub fn back_to_main_menu(
+    actions: Res<Input<Action>>,
+    menu_screen_const: Res<State<MenuScreen>>,
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    rebind_capture: Option<Res<RebindCapture>>,
+) {
+    if rebind_capture.is_some_and(|capture| capture.action.is_some()) {
+        return;
+    }
+    if actions.just_pressed(Action::Pause) {
+        match *menu_screen_const.get() {
+            MenuScreen::Main | MenuScreen::NameEntry => {}
+            _ => menu_screen.set(MenuScreen::Main),
+        }
+    }
+}
+
+/// Moves `MenuFocus` through the buttons on screen, top to bottom, with the
+/// move actions, and presses the focused one on Confirm so the existing
+/// button systems handle it just like a click.
+pub fn navigate_menu_buttons(
+    actions: Res<Input<Action>>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut button_query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &mut Interaction,
+            &mut BackgroundColor,
+        ),
+        With<Button>,
+    >,
+) {
+    let mut buttons: Vec<(Entity, Vec3)> = button_query
+        .iter()
+        .map(|(entity, transform, _, _)| (entity, transform.translation()))
+        .collect();
+    if buttons.is_empty() {
+        menu_focus.button = None;
+        return;
+    }
+    buttons.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let focused = menu_focus
+        .button
+        .and_then(|button| buttons.iter().position(|(entity, _)| *entity == button));
+    let step = if actions.any_just_pressed([Action::MoveDown, Action::MoveRight]) {
+        1
+    } else if actions.any_just_pressed([Action::MoveUp, Action::MoveLeft]) {
+        buttons.len().saturating_sub(1)
+    } else {
+        0
+    };
+
+    let index = match focused {
+        Some(index) => (index + step) % buttons.len(),
+        None if step != 0 => 0,
+        None => {
+            menu_focus.button = None;
+            return;
+        }
+    };
+
+    if focused != Some(index) {
+        if let Some(previous) = focused {
+            if let Ok((_, _, interaction, mut background_color)) =
+                button_query.get_mut(buttons[previous].0)
+            {
+                if *interaction == Interaction::None {
+                    *background_color = NORMAL_BUTTON_COLOR.into();
+                }
+            }
+        }
+    }
+
+    let (entity, _) = buttons[index];
+    menu_focus.button = Some(entity);
+    if let Ok((_, _, mut interaction, mut background_color)) = button_query.get_mut(entity) {
+        if actions.just_pressed(Action::Confirm) {
+            *interaction = Interaction::Pressed;
+        } else if *interaction == Interaction::None {
+            *background_color = HOVERED_BUTTON_COLOR.into();
+        }
+    }
+}
+
+pub fn interact_with_high_scores_button(
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<HighScoresButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                menu_screen.set(MenuScreen::HighScores);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn spawn_high_scores_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    high_scores: Res<HighScores>,
+    arena: Res<Arena>,
+) {
+    build_high_scores_menu(&mut commands, &asset_server, &high_scores, &arena);
+}
+
+pub fn despawn_high_scores_menu(
+    mut commands: Commands,
+    high_scores_menu_query: Query<Entity, With<HighScoresMenu>>,
+) {
+    for high_scores_menu_entity in high_scores_menu_query.iter() {
+        commands.entity(high_scores_menu_entity).despawn_recursive();
+    }
+}
+
+/// Holds on to a run that made the table so its player can name it.
+/// Offers name entry for a run that made the table. Replayed runs are read
+/// and dropped rather than skipped, so their `GameOver` is not picked up the
+/// frame after playback ends.
+pub fn queue_high_score(
+    mut commands: Commands,
+    mut reader: EventReader<GameOver>,
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    high_scores: Res<HighScores>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if playback.is_some() {
+        reader.clear();
+        return;
+    }
+
+    if let Some(game_over) = reader.iter().last() {
+        if !high_scores.qualifies(game_over.score) {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        commands.insert_resource(PendingHighScore {
+            entry: HighScore {
+                name: String::new(),
+                score: game_over.score,
+                wave: game_over.wave,
+                seed: game_over.seed,
+                survival_secs: game_over.survival_secs,
+                timestamp,
+            },
+        });
+        menu_screen.set(MenuScreen::NameEntry);
+    }
+}
+
+pub fn spawn_name_entry_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pending_high_score: Res<PendingHighScore>,
+    arena: Res<Arena>,
+) {
+    build_name_entry_menu(&mut commands, &asset_server, &pending_high_score, &arena);
+}
+
+pub fn despawn_name_entry_menu(
+    mut commands: Commands,
+    name_entry_menu_query: Query<Entity, With<NameEntryMenu>>,
+) {
+    for name_entry_menu_entity in name_entry_menu_query.iter() {
+        commands.entity(name_entry_menu_entity).despawn_recursive();
+    }
+}
+
+/// Typing, Backspace and Enter on the name entry page. Enter files the run in
+/// `HighScores`, saves the table and shows it. Runs every frame so keys
+/// pressed during the run never end up in the name. Reads raw keys, since
+/// text entry has no business going through `Bindings`.
+pub fn type_high_score_name(
+    mut commands: Commands,
+    mut character_reader: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    pending_high_score: Option<ResMut<PendingHighScore>>,
+    menu_screen_const: Res<State<MenuScreen>>,
+    mut high_scores: ResMut<HighScores>,
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    mut name_text_query: Query<&mut Text, With<NameEntryText>>,
+) {
+    let Some(mut pending_high_score) = pending_high_score else {
+        character_reader.clear();
+        return;
+    };
+    if *menu_screen_const.get() != MenuScreen::NameEntry {
+        character_reader.clear();
+        return;
+    }
+
+    let name = &mut pending_high_score.entry.name;
+    for event in character_reader.iter() {
+        if (event.char.is_alphanumeric() || event.char == ' ')
+            && name.chars().count() < MAX_NAME_LENGTH
+        {
+            name.push(event.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        name.pop();
+    }
+
+    for mut text in name_text_query.iter_mut() {
+        text.sections[0].value = format!("{}_", name);
+    }
+
+    let gamepad_confirm = gamepad_buttons
+        .get_just_pressed()
+        .any(|button| button.button_type == GamepadButtonType::South);
+    if keyboard_input.just_pressed(KeyCode::Return) || gamepad_confirm {
+        let mut entry = pending_high_score.entry.clone();
+        entry.name = entry.name.trim().to_string();
+        if entry.name.is_empty() {
+            entry.name = DEFAULT_NAME.to_string();
+        }
+        high_scores.insert(entry);
+
+        save_or_warn(&*high_scores, HighScores::FILE);
+
+        commands.remove_resource::<PendingHighScore>();
+        menu_screen.set(MenuScreen::Main);
+    }
+}
+
+pub fn interact_with_options_button(
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<OptionsButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                menu_screen.set(MenuScreen::Options);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn spawn_options_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    arena: Res<Arena>,
+    mut options_cursor: ResMut<OptionsCursor>,
+    mut rebind_capture: ResMut<RebindCapture>,
+) {
+    options_cursor.index = 0;
+    rebind_capture.action = None;
+    build_options_menu(&mut commands, &asset_server, &settings, &arena);
+}
+
+pub fn despawn_options_menu(
+    mut commands: Commands,
+    options_menu_query: Query<Entity, With<OptionsMenu>>,
+) {
+    for options_menu_entity in options_menu_query.iter() {
+        commands.entity(options_menu_entity).despawn_recursive();
+    }
+}
+
+pub fn interact_with_option_buttons(
+    mut settings: ResMut<Settings>,
+    mut options_cursor: ResMut<OptionsCursor>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &OptionButton),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, mut background_color, button) in button_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                button.option.adjust(&mut settings, button.step);
+                options_cursor.index = OptionKind::all()
+                    .iter()
+                    .position(|option| *option == button.option)
+                    .unwrap_or(0);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn interact_with_rebind_buttons(
+    mut rebind_capture: ResMut<RebindCapture>,
+    mut options_cursor: ResMut<OptionsCursor>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &RebindButton),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, mut background_color, button) in button_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                rebind_capture.action = Some(button.action);
+                options_cursor.index = OptionKind::all()
+                    .iter()
+                    .position(|option| *option == OptionKind::Binding(button.action))
+                    .unwrap_or(0);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn interact_with_reset_controls_button(
+    mut settings: ResMut<Settings>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ResetControlsButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                settings.bindings = Bindings::default();
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// MoveUp and MoveDown pick a row, MoveLeft and MoveRight change it, and
+/// Confirm flips toggles or starts rebinding.
+pub fn navigate_options(
+    actions: Res<Input<Action>>,
+    mut settings: ResMut<Settings>,
+    mut options_cursor: ResMut<OptionsCursor>,
+    mut rebind_capture: ResMut<RebindCapture>,
+) {
+    if rebind_capture.action.is_some() {
+        return;
+    }
+
+    let options = OptionKind::all();
+    let rows = options.len();
+    if actions.just_pressed(Action::MoveUp) {
+        options_cursor.index = (options_cursor.index + rows - 1) % rows;
+    }
+    if actions.just_pressed(Action::MoveDown) {
+        options_cursor.index = (options_cursor.index + 1) % rows;
+    }
+
+    let option = options[options_cursor.index];
+    if actions.just_pressed(Action::MoveLeft) {
+        option.adjust(&mut settings, -1);
+    }
+    if actions.just_pressed(Action::MoveRight) {
+        option.adjust(&mut settings, 1);
+    }
+    if actions.just_pressed(Action::Confirm) {
+        match option {
+            OptionKind::Binding(action) => rebind_capture.action = Some(action),
+            option if option.is_toggle() => option.adjust(&mut settings, 1),
+            _ => {}
+        }
+    }
+}
+
+/// Binds the next key pressed to the action in `RebindCapture`. Escape or
+/// any gamepad button cancels. Skips the frame the capture started in, so
+/// the key that started it is not taken as the new binding.
+pub fn capture_rebinding(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut rebind_capture: ResMut<RebindCapture>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(action) = rebind_capture.action else {
+        return;
+    };
+    if rebind_capture.is_changed() {
+        return;
+    }
+
+    if let Some(key) = keyboard_input.get_just_pressed().next() {
+        if *key != KeyCode::Escape {
+            settings.bindings.rebind(action, *key);
+        }
+        rebind_capture.action = None;
+    } else if gamepad_buttons.get_just_pressed().next().is_some() {
+        rebind_capture.action = None;
+    }
+}
+
+pub fn update_options_menu(
+    settings: Res<Settings>,
+    options_cursor: Res<OptionsCursor>,
+    rebind_capture: Res<RebindCapture>,
+    mut option_text_query: Query<(&mut Text, &OptionText)>,
+) {
+    let selected = OptionKind::all()[options_cursor.index];
+    for (mut text, option_text) in option_text_query.iter_mut() {
+        text.sections[0].value = match option_text.option {
+            OptionKind::Binding(action) if rebind_capture.action == Some(action) => {
+                format!("{}: press a key", action.label())
+            }
+            option => option.label(&settings),
+        };
+        text.sections[0].style.color = if option_text.option == selected {
+            SELECTED_TEXT_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+pub fn start_screen_shake(
+    mut reader: EventReader<PlayerHit>,
+    mut screen_shake: ResMut<ScreenShake>,
+    settings: Res<Settings>,
+) {
+    if reader.iter().last().is_some() && settings.screen_shake {
+        screen_shake.timer.reset();
+    }
+}
+
+/// Jitters the camera while a shake lasts. Draws from `thread_rng`, not
+/// `GameRng`, so shaking never changes a run.
+pub fn shake_camera(
+    time: Res<Time>,
+    mut screen_shake: ResMut<ScreenShake>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    screen_shake.timer.tick(time.delta());
+    let strength = SCREEN_SHAKE_STRENGTH * screen_shake.timer.percent_left();
+
+    let mut rng = rand::thread_rng();
+    for mut transform in camera_query.iter_mut() {
+        transform.translation.x = rng.gen_range(-1.0..=1.0) * strength;
+        transform.translation.y = rng.gen_range(-1.0..=1.0) * strength;
+    }
+}
+
+pub fn record_last_run(
+    mut reader: EventReader<GameOver>,
+    mut last_run: ResMut<LastRun>,
+    high_scores: Option<Res<HighScores>>,
+) {
+    if let Some(game_over) = reader.iter().last() {
+        last_run.summary = Some(game_over.clone());
+        last_run.best_before = high_scores
+            .and_then(|high_scores| high_scores.entries.first().map(|entry| entry.score));
+    }
+}
+
+pub fn spawn_game_over_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    last_run: Res<LastRun>,
+    power_ups: Res<PowerUps>,
+    pending_high_score: Option<Res<PendingHighScore>>,
+) {
+    // The name entry goes first; the summary follows once it is done.
+    if pending_high_score.is_some() {
+        return;
+    }
+
+    if let Some(summary) = &last_run.summary {
+        build_game_over_menu(
+            &mut commands,
+            &asset_server,
+            summary,
+            last_run.best_before,
+            &power_ups,
+            &arena,
+        );
+    }
+}
+
+pub fn despawn_game_over_menu(
+    mut commands: Commands,
+    game_over_menu_query: Query<Entity, With<GameOverMenu>>,
+) {
+    for game_over_menu_entity in game_over_menu_query.iter() {
+        commands.entity(game_over_menu_entity).despawn_recursive();
+    }
+}
+
+pub fn interact_with_retry_button(
+    mut game_start_event_writer: EventWriter<GameStart>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RetryButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                game_start_event_writer.send(GameStart {});
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn interact_with_main_menu_button(
+    mut game_state: ResMut<NextState<GameState>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<MainMenuButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                game_state.set(GameState::Menu);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// Writes the run that just ended to the `replays` folder of the data
+/// directory, and says where on the button.
+pub fn interact_with_save_replay_button(
+    recorder: Res<ReplayRecorder>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &Children),
+        (Changed<Interaction>, With<SaveReplayButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    if let Ok((interaction, mut background_color, children)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+
+                let (Some(replay), Some(dir)) = (&recorder.last, data_dir()) else {
+                    return;
+                };
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since_epoch| since_epoch.as_secs());
+                let path = dir
+                    .join("replays")
+                    .join(format!("run-{}-{}.tgr", replay.seed, timestamp));
+                let result =
+                    fs::create_dir_all(dir.join("replays")).and_then(|_| replay.save(&path));
+                let label = match result {
+                    Ok(()) => {
+                        info!("Saved replay to {}", path.display());
+                        "Replay Saved"
+                    }
+                    Err(error) => {
+                        warn!("Could not save replay to {}: {}", path.display(), error);
+                        "Save Failed"
+                    }
+                };
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.sections[0].value = label.to_string();
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+/// Adds a finished run to the lifetime stats and saves them. Replays are
+/// left out, since they repeat a run that was already counted.
+/// Adds each finished run to the lifetime stats. Replayed runs are drained
+/// like in `queue_high_score`.
+pub fn record_lifetime_stats(
+    mut reader: EventReader<GameOver>,
+    run_stats: Res<RunStats>,
+    mut lifetime_stats: ResMut<LifetimeStats>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if playback.is_some() {
+        reader.clear();
+        return;
+    }
+
+    if let Some(game_over) = reader.iter().last() {
+        lifetime_stats.record(game_over, &run_stats);
+
+        save_or_warn(&*lifetime_stats, LifetimeStats::FILE);
+    }
+}
+
+pub fn interact_with_stats_button(
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<StatsButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                menu_screen.set(MenuScreen::Stats);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn spawn_stats_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    lifetime_stats: Res<LifetimeStats>,
+    power_ups: Res<PowerUps>,
+    arena: Res<Arena>,
+) {
+    build_stats_menu(
+        &mut commands,
+        &asset_server,
+        &lifetime_stats,
+        &power_ups,
+        &arena,
+    );
+}
+
+pub fn despawn_stats_menu(
+    mut commands: Commands,
+    stats_menu_query: Query<Entity, With<StatsMenu>>,
+) {
+    for stats_menu_entity in stats_menu_query.iter() {
+        commands.entity(stats_menu_entity).despawn_recursive();
+    }
+}
+
+/// Feeds `AchievementProgress` from gameplay events. Replayed runs are
+/// drained like in `queue_high_score`.
+pub fn track_achievement_progress(
+    mut game_start_reader: EventReader<GameStart>,
+    mut star_reader: EventReader<StarCollected>,
+    mut power_up_reader: EventReader<PowerUpCollected>,
+    mut player_hit_reader: EventReader<PlayerHit>,
+    mut wave_started_reader: EventReader<WaveStarted>,
+    mut game_over_reader: EventReader<GameOver>,
+    mut progress: ResMut<AchievementProgress>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if playback.is_some() {
+        game_start_reader.clear();
+        star_reader.clear();
+        power_up_reader.clear();
+        player_hit_reader.clear();
+        wave_started_reader.clear();
+        game_over_reader.clear();
+        return;
+    }
+
+    if game_start_reader.iter().last().is_some() {
+        *progress = AchievementProgress::default();
+    }
+
+    progress.money += star_reader.iter().count() as u32;
+    for power_up_collected in power_up_reader.iter() {
+        let name = &power_up_collected.name;
+        *progress.power_ups.entry(name.clone()).or_default() += 1;
+        *progress.streaks.entry(name.clone()).or_default() += 1;
+    }
+    if player_hit_reader.iter().last().is_some() {
+        progress.streaks.clear();
+        progress
+            .crowd_secs
+            .values_mut()
+            .for_each(|secs| *secs = 0.0);
+    }
+    if let Some(wave_started) = wave_started_reader.iter().last() {
+        progress.wave = progress.wave.max(wave_started.number);
+    }
+    if let Some(game_over) = game_over_reader.iter().last() {
+        progress.final_score = Some(game_over.score);
+    }
+}
+
+/// Times the `Crowd` goals: how long the player has gone untouched with at
+/// least that many agents around. Hits reset the clocks through
+/// `track_achievement_progress`.
+pub fn time_crowd_achievements(
+    achievements: Res<Achievements>,
+    enemy_query: Query<(), With<Enemy>>,
+    fixed_time: Res<FixedTime>,
+    mut progress: ResMut<AchievementProgress>,
+) {
+    let on_screen = enemy_query.iter().len() as u32;
+    for achievement in &achievements.achievements {
+        if let AchievementGoal::Crowd { agents, .. } = achievement.goal {
+            let secs = progress.crowd_secs.entry(agents).or_insert(0.0);
+            if on_screen >= agents {
+                *secs += fixed_time.period.as_secs_f32();
+            } else {
+                *secs = 0.0;
+            }
+        }
+    }
+}
+
+/// Unlocks every achievement whose goal has been met, saves the unlocks and
+/// announces each with a toast.
+pub fn unlock_achievements(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    achievements: Res<Achievements>,
+    progress: Res<AchievementProgress>,
+    mut unlocked: ResMut<UnlockedAchievements>,
+    toast_query: Query<(), With<Toast>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if playback.is_some() || !progress.is_changed() {
+        return;
+    }
+
+    let mut toasts = toast_query.iter().len();
+    let mut newly_unlocked = false;
+    for achievement in &achievements.achievements {
+        if unlocked.ids.contains(&achievement.id) || !progress.is_met(&achievement.goal) {
+            continue;
+        }
+        unlocked.ids.insert(achievement.id.clone());
+        newly_unlocked = true;
+        info!("Achievement unlocked: {}", achievement.name);
+
+        let text_style = TextStyle {
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 40.0,
+            color: SELECTED_TEXT_COLOR,
+        };
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("Achievement unlocked: {}", achievement.name),
+                    text_style,
+                ),
+                transform: Transform::from_xyz(
+                    0.0,
+                    arena.height / 2.0 - 120.0 - toasts as f32 * 50.0,
+                    2.0,
+                ),
+                ..default()
+            },
+            Toast {
+                timer: Timer::from_seconds(TOAST_SECONDS, TimerMode::Once),
+            },
+        ));
+        toasts += 1;
+    }
+
+    if newly_unlocked {
+        save_or_warn(&*unlocked, UnlockedAchievements::FILE);
+    }
+}
+
+pub fn tick_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toast_query: Query<(Entity, &mut Toast)>,
+) {
+    for (toast_entity, mut toast) in toast_query.iter_mut() {
+        toast.timer.tick(time.delta());
+        if toast.timer.finished() {
+            commands.entity(toast_entity).despawn();
+        }
+    }
+}
+
+pub fn interact_with_achievements_button(
+    mut menu_screen: ResMut<NextState<MenuScreen>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<AchievementsButton>),
+    >,
+) {
+    if let Ok((interaction, mut background_color)) = button_query.get_single_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *background_color = PRESSED_BUTTON_COLOR.into();
+                menu_screen.set(MenuScreen::Achievements);
+            }
+            Interaction::Hovered => {
+                *background_color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *background_color = NORMAL_BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+pub fn spawn_achievements_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    achievements: Res<Achievements>,
+    unlocked: Res<UnlockedAchievements>,
+    arena: Res<Arena>,
+) {
+    build_achievements_menu(
+        &mut commands,
+        &asset_server,
+        &achievements,
+        &unlocked,
+        &arena,
+    );
+}
+
+pub fn despawn_achievements_menu(
+    mut commands: Commands,
+    achievements_menu_query: Query<Entity, With<AchievementsMenu>>,
+) {
+    for achievements_menu_entity in achievements_menu_query.iter() {
+        commands
+            .entity(achievements_menu_entity)
+            .despawn_recursive();
     }
 }
@@ -1,153 +1,822 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{
+    audio::Volume,
+    prelude::*,
+    window::{PresentMode, WindowMode},
+};
+use rand::Rng;
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::components::*;
+use crate::events::GameOver;
 use crate::resources::*;
 use crate::styles::*;
 
+/// Seconds per gameplay tick.
+pub const TIME_STEP: f32 = 1.0 / 60.0;
 pub const PLAYER_SPEED: f32 = 500.0;
 pub const PLAYER_SIZE: f32 = 64.0;
 pub const ENEMY_SIZE: f32 = 64.0;
 pub const ENEMY_SPEED: f32 = 200.0;
+/// Chasers speed up with the waves like everyone else, but never past this.
+pub const CHASER_MAX_SPEED: f32 = 300.0;
+/// Radians per second a chaser can turn toward the player.
+pub const CHASER_TURN_RATE: f32 = 1.5;
 pub const NUMBER_OF_STARS: usize = 4;
+pub const MAX_NAME_LENGTH: usize = 12;
+pub const SCREEN_SHAKE_SECONDS: f32 = 0.3;
+/// How far, in pixels, the camera strays at the start of a shake.
+pub const SCREEN_SHAKE_STRENGTH: f32 = 12.0;
+pub const STICK_DEADZONE: f32 = 0.2;
+/// Agents passing closer than this without a hit count as near misses.
+pub const NEAR_MISS_DISTANCE: f32 = PLAYER_SIZE * 1.5;
+pub const TOAST_SECONDS: f32 = 3.0;
+/// Money collected within this many seconds of the last raises the combo.
+pub const COMBO_WINDOW_SECONDS: f32 = 1.5;
+pub const COMBO_MAX_MULTIPLIER: u32 = 5;
+/// How much higher `cha.ogg` plays for each step of the combo.
+pub const COMBO_PITCH_STEP: f32 = 0.1;
+pub const COMBO_METER_WIDTH: f32 = 120.0;
+pub const SCORE_POPUP_SECONDS: f32 = 0.8;
+/// Pixels per second money moves toward a magnet at the edge of its reach,
+/// rising to `MAGNET_MAX_SPEED` right next to the player.
+pub const MAGNET_MIN_SPEED: f32 = 150.0;
+pub const MAGNET_MAX_SPEED: f32 = 1200.0;
+/// Playback speed, and so pitch, of the music while time is slowed.
+pub const TIME_SLOW_MUSIC_SPEED: f32 = 0.75;
+/// How much colour slowed agents lose, from 0 (none) to 1 (all).
+pub const TIME_SLOW_DESATURATION: f32 = 0.8;
+/// Pixels per second a score popup rises.
+pub const SCORE_POPUP_SPEED: f32 = 60.0;
+/// The name filed for a high score when none is typed.
+pub const DEFAULT_NAME: &str = "Top G";
+/// Seconds the "Wave N" banner stays up once a wave has begun.
+pub const WAVE_BANNER_SECONDS: f32 = 2.0;
+pub const RECOVERY_SECONDS: f32 = 2.0;
+pub const BLINK_SECONDS: f32 = 0.1;
+pub const KNOCKBACK_SPEED: f32 = 1500.0;
+pub const KNOCKBACK_DECAY: f32 = 0.85;
+/// How far a dash carries the player, in pixels.
+pub const DASH_DISTANCE: f32 = PLAYER_SIZE * 4.0;
+pub const DASH_SECONDS: f32 = 0.15;
+pub const DASH_SPEED: f32 = DASH_DISTANCE / DASH_SECONDS;
+/// Seconds from the start of one dash until the next is ready.
+pub const DASH_COOLDOWN_SECONDS: f32 = 1.5;
+pub const SHIELD_COLOR: Color = Color::rgb(0.45, 0.8, 1.0);
+pub const SHIELD_BURST_SECONDS: f32 = 0.4;
+/// Invulnerability after a shield takes a hit, so agents piled on the
+/// player cannot take a life on the very next tick.
+pub const SHIELD_RECOVERY_SECONDS: f32 = 0.5;
+
+/// Where saves live: `$XDG_DATA_HOME/top-g-simulator` (falling back to
+/// `~/.local/share`), `%APPDATA%` on Windows or `~/Library/Application
+/// Support` on macOS. `None` if the environment gives no hint.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    base.map(|base| base.join("top-g-simulator"))
+}
+
+/// Reads a RON file, reporting a file that does not parse as `InvalidData`.
+pub fn load_ron<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    ron::from_str(&fs::read_to_string(path)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Writes `value` as pretty RON, creating the directories above it first.
+pub fn save_ron<T: Serialize>(value: &T, path: &Path) -> io::Result<()> {
+    let ron = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, ron)
+}
+
+/// Loads `file` from the data directory. A missing file is a fresh start; a
+/// broken one is logged and replaced by the default.
+pub fn load_or_default<T: DeserializeOwned + Default>(file: &str) -> T {
+    match data_dir().map(|dir| dir.join(file)) {
+        Some(path) if path.exists() => load_ron(&path).unwrap_or_else(|error| {
+            warn!("Could not load {}: {}", path.display(), error);
+            T::default()
+        }),
+        _ => T::default(),
+    }
+}
+
+/// Saves `value` as `file` in the data directory, logging if it cannot.
+pub fn save_or_warn<T: Serialize>(value: &T, file: &str) {
+    if let Some(path) = data_dir().map(|dir| dir.join(file)) {
+        if let Err(error) = save_ron(value, &path) {
+            warn!("Could not save {}: {}", path.display(), error);
+        }
+    }
+}
+
+/// Where `settings.ron` is read from and written to.
+pub fn settings_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(Settings::FILE))
+}
+
+/// Loads the settings, falling back to the defaults if the file is missing or
+/// broken. The error comes back alongside so it can be logged once logging
+/// is up.
+pub fn load_settings() -> (Settings, Option<String>) {
+    let Some(path) = settings_path() else {
+        return (
+            Settings::default(),
+            Some("no data directory for settings.ron".to_string()),
+        );
+    };
+    match load_ron(&path) {
+        Ok(settings) => (settings, None),
+        Err(error) => (
+            Settings::default(),
+            Some(format!("Could not load {}: {}", path.display(), error)),
+        ),
+    }
+}
+
+pub fn window_mode(display_mode: DisplayMode) -> WindowMode {
+    match display_mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::BorderlessFullscreen => WindowMode::BorderlessFullscreen,
+        DisplayMode::Fullscreen => WindowMode::Fullscreen,
+    }
+}
+
+pub fn present_mode(vsync: bool) -> PresentMode {
+    if vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    }
+}
+
+pub fn music_playback(settings: &Settings) -> PlaybackSettings {
+    PlaybackSettings::ONCE.with_volume(Volume::new_relative(settings.music_volume))
+}
+
+pub fn sfx_playback(settings: &Settings) -> PlaybackSettings {
+    PlaybackSettings::ONCE.with_volume(Volume::new_relative(settings.sfx_volume))
+}
+
+/// A random point in the arena, far enough from the edges to fit a sprite.
+pub fn random_arena_position(arena: &Arena, game_rng: &mut GameRng) -> Vec2 {
+    let width = (arena.width / 2.0) - (ENEMY_SIZE / 2.0);
+    let height = (arena.height / 2.0) - (ENEMY_SIZE / 2.0);
+
+    let random_x = (game_rng.rng.gen::<f32>() * width * 2.0) - width;
+    let random_y = (game_rng.rng.gen::<f32>() * height * 2.0) - height;
+    Vec2::new(random_x, random_y)
+}
+
+/// How long until the next `power_up` appears during `wave`.
+pub fn power_up_delay(
+    power_up: &PowerUp,
+    wave: &WaveDefinition,
+    game_rng: &mut GameRng,
+) -> Duration {
+    let (shortest, longest) = power_up.spawn_interval;
+    let secs = shortest + game_rng.rng.gen::<f32>() * (longest - shortest);
+    Duration::from_secs_f32(secs * wave.power_up_interval)
+}
+
+/// How an agent of `kind` is drawn, greyed out while time is slowed.
+pub fn agent_color(kind: &EnemyKind, enemy_time_scale: &EnemyTimeScale) -> Color {
+    if enemy_time_scale.value < 1.0 {
+        desaturate(kind.tint(), TIME_SLOW_DESATURATION)
+    } else {
+        kind.tint()
+    }
+}
+
+/// `color` moved `amount` of the way toward the grey of the same brightness.
+pub fn desaturate(color: Color, amount: f32) -> Color {
+    let [red, green, blue, alpha] = color.as_rgba_f32();
+    let grey = 0.299 * red + 0.587 * green + 0.114 * blue;
+    Color::rgba(
+        red + (grey - red) * amount,
+        green + (grey - green) * amount,
+        blue + (grey - blue) * amount,
+        alpha,
+    )
+}
+
+/// Playback speed for the money sound, climbing with the combo.
+pub fn combo_pitch(multiplier: u32) -> f32 {
+    1.0 + multiplier.saturating_sub(1) as f32 * COMBO_PITCH_STEP
+}
+
+/// `YYYY-MM-DD` for a Unix timestamp, in UTC.
+pub fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm.
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Applies a radial deadzone to a stick position and rescales what is left,
+/// so movement starts from zero at the edge of the deadzone and reaches full
+/// speed at full tilt.
+pub fn stick_direction(stick: Vec2) -> Vec2 {
+    let tilt = stick.length();
+    if tilt <= STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+    let scaled = ((tilt - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+    stick / tilt * scaled
+}
+
+/// `M:SS` for a number of seconds.
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
 
 pub fn is_collision(enemy_x: f32, enemy_y: f32, player_x: f32, player_y: f32) -> bool {
-    return ((enemy_x - player_x).powi(2) + (enemy_y - player_y).powi(2)).sqrt() <= PLAYER_SIZE;
+    ((enemy_x - player_x).powi(2) + (enemy_y - player_y).powi(2)).sqrt() <= PLAYER_SIZE
+}
+
+/// Like `is_collision`, for a player that moved from `from` to `to` this
+/// tick: true if `point` was touched anywhere along the way, so a dash can't
+/// skip over it.
+pub fn is_collision_along(point: Vec2, from: Vec2, to: Vec2) -> bool {
+    let path = to - from;
+    let along = if path == Vec2::ZERO {
+        0.0
+    } else {
+        ((point - from).dot(path) / path.length_squared()).clamp(0.0, 1.0)
+    };
+    point.distance(from + path * along) <= PLAYER_SIZE
+}
+
+/// Picks the kind of the next agent according to a wave's `mix`.
+pub fn pick_enemy_kind(mix: &[(EnemyKind, u32)], game_rng: &mut ResMut<GameRng>) -> EnemyKind {
+    let total: u32 = mix.iter().map(|(_kind, weight)| weight).sum();
+    if total == 0 {
+        return EnemyKind::Bouncer;
+    }
+
+    let mut roll = game_rng.rng.gen_range(0..total);
+    for &(kind, weight) in mix {
+        if roll < weight {
+            return kind;
+        }
+        roll -= weight;
+    }
+    EnemyKind::Bouncer
+}
+
+/// Spawns an agent on a random edge of the arena, clear of the player.
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    arena: &Res<Arena>,
+    game_rng: &mut ResMut<GameRng>,
+    player_transform: Option<&Transform>,
+    kind: EnemyKind,
+) {
+    let width = (arena.width / 2.0) - (ENEMY_SIZE / 2.0);
+    let height = (arena.height / 2.0) - (ENEMY_SIZE / 2.0);
+
+    let edge = game_rng.rng.gen::<u8>() % 4;
+    let (mut random_x, mut random_y) = match edge {
+        0 => ((game_rng.rng.gen::<f32>() * width * 2.0) - width, height),
+        1 => ((game_rng.rng.gen::<f32>() * width * 2.0) - width, -height),
+        2 => (width, (game_rng.rng.gen::<f32>() * height * 2.0) - height),
+        _ => (-width, (game_rng.rng.gen::<f32>() * height * 2.0) - height),
+    };
+
+    if let Some(player_transform) = player_transform {
+        let player_x: f32 = player_transform.translation.x;
+        let player_y: f32 = player_transform.translation.y;
+
+        while is_collision(random_x, random_y, player_x, player_y) {
+            let (new_x, new_y) = match edge {
+                0 => ((game_rng.rng.gen::<f32>() * width * 2.0) - width, height),
+                1 => ((game_rng.rng.gen::<f32>() * width * 2.0) - width, -height),
+                2 => (width, (game_rng.rng.gen::<f32>() * height * 2.0) - height),
+                _ => (-width, (game_rng.rng.gen::<f32>() * height * 2.0) - height),
+            };
+            random_x = new_x;
+            random_y = new_y;
+        }
+    }
+
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(random_x, random_y, 0.0),
+            sprite: Sprite {
+                color: kind.tint(),
+                ..default()
+            },
+            texture: asset_server.load("sprites/agent.png"),
+            ..default()
+        },
+        Enemy {
+            direction: Vec2::new(game_rng.rng.gen::<f32>(), game_rng.rng.gen::<f32>()).normalize(),
+        },
+        kind,
+        Interpolated::new(Vec3::new(random_x, random_y, 0.0)),
+    ));
+}
+
+/// The red menu title with its black outline.
+pub fn build_title(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>, title: &str) {
+    parent
+        .spawn(NodeBundle {
+            style: title_style(),
+            ..default()
+        })
+        .with_children(|parent| {
+            // Shadow Text
+            for &offset in &[
+                Vec2::new(-1.0, -1.0),
+                Vec2::new(1.0, -1.0),
+                Vec2::new(-1.0, 1.0),
+                Vec2::new(1.0, 1.0),
+            ] {
+                parent.spawn(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(offset.x),
+                        top: Val::Px(offset.y),
+                        ..Default::default()
+                    },
+                    text: Text {
+                        sections: vec![TextSection::new(
+                            title,
+                            get_shadow_text_style(asset_server),
+                        )],
+                        alignment: TextAlignment::Center,
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+            // Main Text
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection::new(title, get_title_text_style(asset_server))],
+                    alignment: TextAlignment::Center,
+                    ..default()
+                },
+                ..default()
+            });
+        });
+}
+
+pub fn build_button(
+    parent: &mut ChildBuilder,
+    asset_server: &Res<AssetServer>,
+    label: &str,
+    marker: impl Component,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: button_style(),
+                background_color: NORMAL_BUTTON_COLOR.into(),
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection::new(label, get_button_text_style(asset_server))],
+                    alignment: TextAlignment::Center,
+                    ..default()
+                },
+                ..default()
+            });
+        });
+}
+
+pub fn build_text(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>, text: String) {
+    parent.spawn(TextBundle {
+        text: Text {
+            sections: vec![TextSection::new(text, get_score_text_style(asset_server))],
+            alignment: TextAlignment::Center,
+            ..default()
+        },
+        ..default()
+    });
 }
 
 pub fn build_main_menu(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     score: &Res<Score>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    seed: Option<u64>,
+    arena: &Res<Arena>,
 ) -> Entity {
     let main_menu_entity = commands
         .spawn((
             NodeBundle {
-                style: main_menu_style(window_query),
+                style: main_menu_style(arena),
                 ..default()
             },
             MainMenu {},
         ))
         .with_children(|parent| {
-            // === Title ===
-            parent
-                .spawn(NodeBundle {
-                    style: title_style(),
-                    ..default()
-                })
-                .with_children(|parent| {
-                    // Shadow Text
-                    for &offset in &[
-                        Vec2::new(-1.0, -1.0),
-                        Vec2::new(1.0, -1.0),
-                        Vec2::new(-1.0, 1.0),
-                        Vec2::new(1.0, 1.0),
-                    ] {
-                        parent.spawn(TextBundle {
-                            style: Style {
-                                position_type: PositionType::Absolute,
-                                left: Val::Px(offset.x),
-                                top: Val::Px(offset.y),
-                                ..Default::default()
-                            },
-                            text: Text {
-                                sections: vec![TextSection::new(
-                                    "Top G Simulator",
-                                    get_shadow_text_style(&asset_server),
-                                )],
-                                alignment: TextAlignment::Center,
-                                ..default()
-                            },
-                            ..default()
-                        });
-                    }
-                    // Main Text
-                    parent.spawn(TextBundle {
-                        text: Text {
-                            sections: vec![TextSection::new(
-                                "Top G Simulator",
-                                get_title_text_style(&asset_server),
-                            )],
-                            alignment: TextAlignment::Center,
-                            ..default()
-                        },
-                        ..default()
-                    });
-                });
-            // === Play Button ===
-            parent
-                .spawn((
-                    ButtonBundle {
-                        style: button_style(),
-                        background_color: NORMAL_BUTTON_COLOR.into(),
-                        ..default()
-                    },
-                    PlayButton {},
-                ))
-                .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text {
-                            sections: vec![TextSection::new(
-                                "Play",
-                                get_button_text_style(&asset_server),
-                            )],
-                            alignment: TextAlignment::Center,
-                            ..default()
-                        },
+            build_title(parent, asset_server, "Top G Simulator");
+            build_button(parent, asset_server, "Play", PlayButton {});
+            build_button(parent, asset_server, "High Scores", HighScoresButton {});
+            build_button(parent, asset_server, "Stats", StatsButton {});
+            build_button(parent, asset_server, "Achievements", AchievementsButton {});
+            build_button(parent, asset_server, "Options", OptionsButton {});
+            build_button(parent, asset_server, "Quit", QuitButton {});
+            build_text(parent, asset_server, format!("Score: ${}", score.value));
+            if let Some(seed) = seed {
+                build_text(parent, asset_server, format!("Seed: {}", seed));
+            }
+        })
+        .id();
+
+    main_menu_entity
+}
+
+pub fn build_high_scores_menu(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    high_scores: &Res<HighScores>,
+    arena: &Res<Arena>,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: main_menu_style(arena),
+                ..default()
+            },
+            HighScoresMenu {},
+        ))
+        .with_children(|parent| {
+            build_title(parent, asset_server, "High Scores");
+            if high_scores.entries.is_empty() {
+                build_text(parent, asset_server, "No runs yet".to_string());
+            }
+            for (rank, entry) in high_scores.entries.iter().enumerate() {
+                build_text(
+                    parent,
+                    asset_server,
+                    format!(
+                        "{}. {}  ${}  wave {}  {}  {}  seed {}",
+                        rank + 1,
+                        entry.name,
+                        entry.score,
+                        entry.wave,
+                        format_duration(entry.survival_secs),
+                        format_date(entry.timestamp),
+                        entry.seed
+                    ),
+                );
+            }
+            build_button(parent, asset_server, "Back", BackButton {});
+        })
+        .id()
+}
+
+/// `Coffee: 2  Cigars: 0` and so on for every power-up, in the order of
+/// `power_ups.ron`.
+pub fn power_up_counts<N: Copy + Default + Display>(
+    counts: &BTreeMap<String, N>,
+    power_ups: &PowerUps,
+) -> String {
+    power_ups
+        .power_ups
+        .iter()
+        .map(|power_up| {
+            let count = counts.get(&power_up.name).copied().unwrap_or_default();
+            format!("{}: {}", power_up.name, count)
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+pub fn build_stats_menu(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    stats: &Res<LifetimeStats>,
+    power_ups: &PowerUps,
+    arena: &Res<Arena>,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: main_menu_style(arena),
+                ..default()
+            },
+            StatsMenu {},
+        ))
+        .with_children(|parent| {
+            build_title(parent, asset_server, "Stats");
+            if stats.runs == 0 {
+                build_text(parent, asset_server, "No runs yet".to_string());
+            } else {
+                let lines = [
+                    format!(
+                        "Runs: {}  Time played: {}",
+                        stats.runs,
+                        format_duration(stats.survival_secs as f32)
+                    ),
+                    format!("Money: {}", stats.money),
+                    power_up_counts(&stats.power_ups, power_ups),
+                    format!(
+                        "Hits taken: {}  Near misses: {}",
+                        stats.hits, stats.near_misses
+                    ),
+                    format!(
+                        "Distance: {:.0} px  Invincible for {}",
+                        stats.distance,
+                        format_duration(stats.invincible_secs as f32)
+                    ),
+                    format!(
+                        "Average: ${:.1} over {}",
+                        stats.average_score(),
+                        format_duration(stats.average_survival_secs())
+                    ),
+                    format!(
+                        "Best: ${}  wave {}  {}",
+                        stats.best_score,
+                        stats.best_wave,
+                        format_duration(stats.longest_survival_secs)
+                    ),
+                    format!(
+                        "Furthest: {:.0} px  Most near misses: {}",
+                        stats.longest_distance, stats.most_near_misses
+                    ),
+                ];
+                for line in lines {
+                    build_text(parent, asset_server, line);
+                }
+            }
+            build_button(parent, asset_server, "Back", BackButton {});
+        })
+        .id()
+}
+
+pub fn build_achievements_menu(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    achievements: &Res<Achievements>,
+    unlocked: &Res<UnlockedAchievements>,
+    arena: &Res<Arena>,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: main_menu_style(arena),
+                ..default()
+            },
+            AchievementsMenu {},
+        ))
+        .with_children(|parent| {
+            build_title(parent, asset_server, "Achievements");
+            let unlocked_count = achievements
+                .achievements
+                .iter()
+                .filter(|achievement| unlocked.ids.contains(&achievement.id))
+                .count();
+            build_text(
+                parent,
+                asset_server,
+                format!(
+                    "{} of {} unlocked",
+                    unlocked_count,
+                    achievements.achievements.len()
+                ),
+            );
+            for achievement in &achievements.achievements {
+                let mark = if unlocked.ids.contains(&achievement.id) {
+                    "[x]"
+                } else {
+                    "[ ]"
+                };
+                build_text(
+                    parent,
+                    asset_server,
+                    format!("{} {}: {}", mark, achievement.name, achievement.description),
+                );
+            }
+            build_button(parent, asset_server, "Back", BackButton {});
+        })
+        .id()
+}
+
+pub fn build_game_over_menu(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    summary: &GameOver,
+    best_before: Option<u32>,
+    power_ups: &PowerUps,
+    arena: &Res<Arena>,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: main_menu_style(arena),
+                ..default()
+            },
+            GameOverMenu {},
+        ))
+        .with_children(|parent| {
+            build_title(parent, asset_server, "Game Over");
+            build_text(parent, asset_server, format!("Score: ${}", summary.score));
+            build_text(
+                parent,
+                asset_server,
+                format!(
+                    "Survived {} into wave {}",
+                    format_duration(summary.survival_secs),
+                    summary.wave
+                ),
+            );
+            build_text(
+                parent,
+                asset_server,
+                format!("Agents on screen: {}", summary.agents),
+            );
+            build_text(parent, asset_server, format!("Money: {}", summary.money));
+            build_text(
+                parent,
+                asset_server,
+                power_up_counts(&summary.power_ups, power_ups),
+            );
+            let best = match best_before {
+                Some(best) if summary.score > best => format!("New best! Previous: ${}", best),
+                Some(best) => format!("Best: ${}", best),
+                None => "First run on the board".to_string(),
+            };
+            build_text(parent, asset_server, best);
+            build_text(parent, asset_server, format!("Seed: {}", summary.seed));
+            build_button(parent, asset_server, "Retry", RetryButton {});
+            build_button(parent, asset_server, "Main Menu", MainMenuButton {});
+            build_button(parent, asset_server, "Save Replay", SaveReplayButton {});
+        })
+        .id()
+}
+
+pub fn build_options_menu(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    settings: &Res<Settings>,
+    arena: &Res<Arena>,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: main_menu_style(arena),
+                ..default()
+            },
+            OptionsMenu {},
+        ))
+        .with_children(|parent| {
+            build_title(parent, asset_server, "Options");
+            for option in OptionKind::all() {
+                parent
+                    .spawn(NodeBundle {
+                        style: option_row_style(),
                         ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn((
+                            TextBundle::from_section(
+                                option.label(settings),
+                                get_button_text_style(asset_server),
+                            ),
+                            OptionText { option },
+                        ));
+                        parent
+                            .spawn(NodeBundle {
+                                style: option_buttons_style(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                if let OptionKind::Binding(action) = option {
+                                    build_button(
+                                        parent,
+                                        asset_server,
+                                        "Rebind",
+                                        RebindButton { action },
+                                    );
+                                } else if option.is_toggle() {
+                                    build_button(
+                                        parent,
+                                        asset_server,
+                                        "Toggle",
+                                        OptionButton { option, step: 1 },
+                                    );
+                                } else {
+                                    build_button(
+                                        parent,
+                                        asset_server,
+                                        " - ",
+                                        OptionButton { option, step: -1 },
+                                    );
+                                    build_button(
+                                        parent,
+                                        asset_server,
+                                        " + ",
+                                        OptionButton { option, step: 1 },
+                                    );
+                                }
+                            });
                     });
-                });
-            // === Quit Button ===
-            parent
-                .spawn((
-                    ButtonBundle {
-                        style: button_style(),
-                        background_color: NORMAL_BUTTON_COLOR.into(),
+            }
+            build_text(
+                parent,
+                asset_server,
+                "Up/Down to choose, Left/Right to change, Confirm to rebind".to_string(),
+            );
+            build_button(
+                parent,
+                asset_server,
+                "Reset Controls",
+                ResetControlsButton {},
+            );
+            build_button(parent, asset_server, "Back", BackButton {});
+        })
+        .id()
+}
+
+pub fn build_name_entry_menu(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    pending: &Res<PendingHighScore>,
+    arena: &Res<Arena>,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: main_menu_style(arena),
+                ..default()
+            },
+            NameEntryMenu {},
+        ))
+        .with_children(|parent| {
+            build_title(parent, asset_server, "New High Score!");
+            build_text(
+                parent,
+                asset_server,
+                format!("Score: ${}", pending.entry.score),
+            );
+            build_text(parent, asset_server, "Type your name:".to_string());
+            parent.spawn((
+                TextBundle {
+                    text: Text {
+                        sections: vec![TextSection::new("_", get_title_text_style(asset_server))],
+                        alignment: TextAlignment::Center,
                         ..default()
                     },
-                    QuitButton {},
-                ))
-                .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text {
-                            sections: vec![TextSection::new(
-                                "Quit",
-                                get_button_text_style(&asset_server),
-                            )],
-                            alignment: TextAlignment::Center,
-                            ..default()
-                        },
-                        ..default()
-                    });
-                });
-            parent.spawn(TextBundle {
-                text: Text {
-                    sections: vec![TextSection::new(
-                        format!("Score: ${}", score.value),
-                        get_score_text_style(&asset_server),
-                    )],
-                    alignment: TextAlignment::Center,
                     ..default()
                 },
-                ..default()
-            });
+                NameEntryText {},
+            ));
+            build_text(parent, asset_server, "Press Enter to save".to_string());
         })
-        .id();
-
-    return main_menu_entity;
+        .id()
 }
 
 pub fn build_sound_button(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    window_query: &Query<&Window, With<PrimaryWindow>>,
+    arena: &Res<Arena>,
 ) -> Entity {
-    let window = window_query.single();
-    let button_x = window.width() / 2.0;
-    let button_y = -window.height() + 50.0;
+    let button_x = arena.width / 2.0;
+    let button_y = -arena.height + 50.0;
 
     let button_bundle = ButtonBundle {
-        style: mr_producer_button_style(&window),
+        style: mr_producer_button_style(arena),
         background_color: NORMAL_BUTTON_COLOR.into(),
         border_color: BorderColor(Color::BLACK),
         transform: Transform::from_xyz(button_x, button_y, 0.0),
@@ -158,7 +827,7 @@ pub fn build_sound_button(
         text: Text {
             sections: vec![TextSection::new(
                 "Toggle Tune",
-                get_button_text_style(&asset_server),
+                get_button_text_style(asset_server),
             )],
             alignment: TextAlignment::Center,
             ..default()
@@ -166,11 +835,10 @@ pub fn build_sound_button(
         ..default()
     };
 
-    return commands
+    commands
         .spawn((button_bundle, SoundButton {}))
         .with_children(|parent| {
             parent.spawn(text_bundle);
         })
-        .id();
+        .id()
 }
-
@@ -0,0 +1,71 @@
+use std::{collections::BTreeSet, path::Path};
+
+use top_g_simulator::resources::*;
+
+#[test]
+fn the_built_in_achievements_parse_with_unique_ids_and_real_power_ups() {
+    let achievements = Achievements::default();
+    assert!(!achievements.achievements.is_empty());
+
+    let ids: BTreeSet<&str> = achievements
+        .achievements
+        .iter()
+        .map(|achievement| achievement.id.as_str())
+        .collect();
+    assert_eq!(ids.len(), achievements.achievements.len());
+
+    let power_ups = PowerUps::default();
+    for achievement in &achievements.achievements {
+        if let AchievementGoal::Collect { power_up, .. }
+        | AchievementGoal::Streak { power_up, .. } = &achievement.goal
+        {
+            assert!(power_ups.get(power_up).is_some(), "{}", achievement.id);
+        }
+    }
+}
+
+#[test]
+fn goals_are_met_by_the_progress_of_a_run() {
+    let mut progress = AchievementProgress::default();
+    let crowd = AchievementGoal::Crowd {
+        agents: 5,
+        secs: 20.0,
+    };
+    let streak = AchievementGoal::Streak {
+        power_up: "Coffee".to_string(),
+        count: 3,
+    };
+    assert!(!progress.is_met(&AchievementGoal::Money(1)));
+    assert!(!progress.is_met(&AchievementGoal::Score(0)));
+    assert!(!progress.is_met(&crowd));
+
+    progress.money = 3;
+    progress.streaks.insert("Coffee".to_string(), 2);
+    progress.power_ups.insert("Cigars".to_string(), 1);
+    progress.wave = 4;
+    progress.final_score = Some(120);
+    progress.crowd_secs.insert(5, 20.0);
+    assert!(progress.is_met(&AchievementGoal::Money(3)));
+    assert!(!progress.is_met(&streak));
+    assert!(progress.is_met(&AchievementGoal::Collect {
+        power_up: "Cigars".to_string(),
+        count: 1
+    }));
+    assert!(progress.is_met(&AchievementGoal::Wave(3)));
+    assert!(progress.is_met(&AchievementGoal::Score(100)));
+    assert!(progress.is_met(&crowd));
+    assert!(!progress.is_met(&AchievementGoal::Crowd {
+        agents: 6,
+        secs: 1.0
+    }));
+}
+
+#[test]
+fn unlocks_are_saved_apart_from_the_achievement_list() {
+    let list = Path::new(Achievements::PATH).file_name().unwrap();
+    assert_ne!(list, UnlockedAchievements::FILE);
+
+    let unlocked: UnlockedAchievements =
+        ron::from_str(r#"(ids: ["first_dollar", "first_dollar"])"#).unwrap();
+    assert_eq!(unlocked.ids.len(), 1);
+}
@@ -0,0 +1,886 @@
+use bevy::{
+    input::gamepad::{
+        GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection,
+        GamepadConnectionEvent, GamepadEvent, GamepadInfo,
+    },
+    prelude::*,
+};
+
+use top_g_simulator::{components::*, events::*, plugins::*, resources::*, utils::*};
+
+const SEED: u64 = 1234;
+
+/// A headless game with a fixed seed, already in `GameState::Game` with the
+/// player spawned at the origin.
+fn start_game() -> App {
+    start_game_with(|_app| {})
+}
+
+/// Like `start_game`, with a chance to insert resources before the plugins
+/// are added.
+fn start_game_with(setup: impl FnOnce(&mut App)) -> App {
+    let mut app = App::new();
+    setup(&mut app);
+    app.insert_resource(GameRng::new(Some(SEED)))
+        .add_plugins(HeadlessPlugin);
+    app.update();
+
+    app.world.send_event(GameStart {});
+    app.update();
+    app.update();
+
+    assert_eq!(
+        *app.world.resource::<State<GameState>>().get(),
+        GameState::Game
+    );
+    app
+}
+
+fn advance(app: &mut App, ticks: usize) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+fn player_translation(app: &mut App) -> Vec3 {
+    app.world
+        .query_filtered::<&Transform, With<Player>>()
+        .single(&app.world)
+        .translation
+}
+
+fn count<T: Component>(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<T>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn game_over_count(app: &App) -> usize {
+    let events = app.world.resource::<Events<GameOver>>();
+    events.get_reader().iter(events).count()
+}
+
+fn last_game_over_wave(app: &App) -> Option<u32> {
+    let events = app.world.resource::<Events<GameOver>>();
+    events
+        .get_reader()
+        .iter(events)
+        .last()
+        .map(|game_over| game_over.wave)
+}
+
+fn spawn_near_player<T: Component>(app: &mut App, component: T) {
+    let translation = player_translation(app);
+    app.world
+        .spawn((Transform::from_translation(translation), component));
+}
+
+fn clear_enemies(app: &mut App) {
+    let enemies: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, With<Enemy>>()
+        .iter(&app.world)
+        .collect();
+    for enemy in enemies {
+        app.world.despawn(enemy);
+    }
+}
+
+#[test]
+fn held_keys_move_the_player() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+    advance(&mut app, 30);
+
+    let translation = player_translation(&mut app);
+    assert!(
+        translation.x > 200.0,
+        "player only reached {}",
+        translation.x
+    );
+    assert_eq!(translation.y, 0.0);
+}
+
+#[test]
+fn rebound_keys_move_the_player() {
+    let mut app = start_game_with(|app| {
+        let mut bindings = Bindings::default();
+        bindings.rebind(Action::MoveRight, KeyCode::L);
+        app.insert_resource(bindings);
+    });
+    clear_enemies(&mut app);
+
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+    advance(&mut app, 10);
+    assert_eq!(player_translation(&mut app).x, 0.0);
+
+    let mut keyboard_input = app.world.resource_mut::<Input<KeyCode>>();
+    keyboard_input.release(KeyCode::D);
+    keyboard_input.press(KeyCode::L);
+    advance(&mut app, 10);
+    assert!(player_translation(&mut app).x > 0.0);
+}
+
+#[test]
+fn mouse_control_follows_the_cursor_inside_the_arena() {
+    let mut app = start_game_with(|app| {
+        app.insert_resource(ControlMode::Mouse);
+        app.insert_resource(MouseTarget {
+            position: Some(Vec2::new(200.0, -100.0)),
+        });
+    });
+    clear_enemies(&mut app);
+
+    advance(&mut app, 60);
+    let translation = player_translation(&mut app);
+    assert!(
+        (translation.x - 200.0).abs() < 2.0,
+        "x is {}",
+        translation.x
+    );
+    assert!(
+        (translation.y + 100.0).abs() < 2.0,
+        "y is {}",
+        translation.y
+    );
+
+    app.world.resource_mut::<MouseTarget>().position = Some(Vec2::new(5000.0, -100.0));
+    advance(&mut app, 300);
+    let arena_edge = app.world.resource::<Arena>().width / 2.0 - PLAYER_SIZE / 2.0;
+    assert_eq!(player_translation(&mut app).x, arena_edge);
+}
+
+fn connect_gamepad(app: &mut App) -> Gamepad {
+    let gamepad = Gamepad::new(0);
+    app.world
+        .send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: "Test Pad".to_string(),
+            }),
+        )));
+    app.update();
+    gamepad
+}
+
+#[test]
+fn the_left_stick_moves_the_player_in_proportion_to_tilt() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    let gamepad = connect_gamepad(&mut app);
+    let start = player_translation(&mut app);
+
+    app.world
+        .send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            0.1,
+        )));
+    advance(&mut app, 10);
+    assert_eq!(player_translation(&mut app), start);
+
+    app.world
+        .send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            0.6,
+        )));
+    advance(&mut app, 10);
+    let half_tilt = player_translation(&mut app).x - start.x;
+
+    app.world
+        .send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            1.0,
+        )));
+    app.update();
+    let before = player_translation(&mut app).x;
+    advance(&mut app, 10);
+    let full_tilt = player_translation(&mut app).x - before;
+
+    assert!(half_tilt > 0.0);
+    assert!(
+        (half_tilt / full_tilt - 0.5).abs() < 0.05,
+        "half tilt moved {} against {} at full tilt",
+        half_tilt,
+        full_tilt
+    );
+}
+
+#[test]
+fn gamepad_buttons_press_their_actions() {
+    let mut app = start_game();
+    let gamepad = connect_gamepad(&mut app);
+
+    app.world
+        .send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(
+            gamepad,
+            GamepadButtonType::Start,
+            1.0,
+        )));
+    app.update();
+
+    let actions = app.world.resource::<Input<Action>>();
+    assert!(actions.just_pressed(Action::Pause));
+    assert!(!actions.pressed(Action::Confirm));
+}
+
+#[test]
+fn collecting_a_star_increments_score() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    let score = app.world.resource::<Score>().value;
+
+    spawn_near_player(&mut app, Star {});
+    advance(&mut app, 1);
+
+    assert_eq!(app.world.resource::<Score>().value, score + 1);
+}
+
+#[test]
+fn quick_pickups_build_a_combo_that_decays() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    let score = app.world.resource::<Score>().value;
+
+    for _ in 0..3 {
+        spawn_near_player(&mut app, Star {});
+        advance(&mut app, 10);
+    }
+    assert_eq!(app.world.resource::<Combo>().multiplier, 3);
+    assert_eq!(app.world.resource::<Score>().value, score + 1 + 2 + 3);
+
+    let window = (COMBO_WINDOW_SECONDS / TIME_STEP) as usize + 1;
+    advance(&mut app, window);
+    assert_eq!(app.world.resource::<Combo>().multiplier, 2);
+    advance(&mut app, window);
+    assert_eq!(app.world.resource::<Combo>().multiplier, 1);
+
+    spawn_near_player(&mut app, Star {});
+    advance(&mut app, 1);
+    assert_eq!(app.world.resource::<Score>().value, score + 7);
+}
+
+fn player_lives(app: &mut App) -> u32 {
+    app.world
+        .query_filtered::<&Lives, With<Player>>()
+        .single(&app.world)
+        .value
+}
+
+#[test]
+fn touching_an_enemy_costs_a_life() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    assert_eq!(player_lives(&mut app), StartingLives::default().value);
+
+    app.world.spawn((
+        Transform::from_xyz(-10.0, 0.0, 0.0),
+        Enemy { direction: Vec2::Y },
+    ));
+    advance(&mut app, 1);
+
+    assert_eq!(game_over_count(&app), 0);
+    assert_eq!(player_lives(&mut app), StartingLives::default().value - 1);
+    assert_eq!(count::<Recovering>(&mut app), 1);
+
+    // Knocked away from the agent, and not hit again while recovering.
+    advance(&mut app, 10);
+    assert!(player_translation(&mut app).x > PLAYER_SIZE);
+    clear_enemies(&mut app);
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+    assert_eq!(player_lives(&mut app), StartingLives::default().value - 1);
+
+    clear_enemies(&mut app);
+    advance(&mut app, (RECOVERY_SECONDS * 60.0) as usize);
+    assert_eq!(count::<Recovering>(&mut app), 0);
+}
+
+#[test]
+fn losing_the_last_life_ends_the_game() {
+    let mut app = start_game();
+    app.world
+        .query_filtered::<&mut Lives, With<Player>>()
+        .single_mut(&mut app.world)
+        .value = 1;
+
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+
+    assert_eq!(game_over_count(&app), 1);
+    assert_eq!(last_game_over_wave(&app), Some(1));
+    assert_eq!(count::<Player>(&mut app), 0);
+
+    advance(&mut app, 1);
+    assert_eq!(
+        *app.world.resource::<State<GameState>>().get(),
+        GameState::GameOver
+    );
+}
+
+#[test]
+fn the_game_over_summary_describes_the_run() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    spawn_near_player(&mut app, Star {});
+    spawn_near_player(&mut app, Star {});
+    advance(&mut app, 30);
+
+    app.world
+        .query_filtered::<&mut Lives, With<Player>>()
+        .single_mut(&mut app.world)
+        .value = 1;
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+
+    let events = app.world.resource::<Events<GameOver>>();
+    let summary = events.get_reader().iter(events).last().unwrap().clone();
+    // Both in the same tick, so the second counts double.
+    assert_eq!(summary.score, 3);
+    assert_eq!(summary.money, 2);
+    assert!(summary.power_ups.is_empty());
+    assert_eq!(summary.agents, count::<Enemy>(&mut app) as u32);
+    assert!(summary.agents >= 1);
+    assert!(summary.survival_secs >= 0.5);
+    assert_eq!(summary.seed, SEED);
+}
+
+#[test]
+fn run_stats_count_distance_near_misses_and_hits() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+    advance(&mut app, 30);
+    app.world
+        .resource_mut::<Input<KeyCode>>()
+        .release(KeyCode::D);
+    advance(&mut app, 1);
+    let distance = app.world.resource::<RunStats>().distance;
+    assert!((distance - 250.0).abs() < 1.0, "travelled {}", distance);
+
+    let translation = player_translation(&mut app);
+    app.world.spawn((
+        Transform::from_translation(translation + Vec3::new(80.0, 0.0, 0.0)),
+        Enemy { direction: Vec2::X },
+    ));
+    advance(&mut app, 20);
+    assert_eq!(app.world.resource::<RunStats>().near_misses, 1);
+    assert_eq!(app.world.resource::<RunStats>().hits, 0);
+
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+    assert_eq!(app.world.resource::<RunStats>().hits, 1);
+}
+
+#[test]
+fn retrying_from_game_over_starts_a_fresh_run() {
+    let mut app = start_game();
+    app.world
+        .query_filtered::<&mut Lives, With<Player>>()
+        .single_mut(&mut app.world)
+        .value = 1;
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 2);
+    assert_eq!(count::<Player>(&mut app), 0);
+
+    app.world.send_event(GameStart {});
+    advance(&mut app, 2);
+
+    assert_eq!(
+        *app.world.resource::<State<GameState>>().get(),
+        GameState::Game
+    );
+    assert_eq!(count::<Player>(&mut app), 1);
+    assert_eq!(app.world.resource::<Score>().value, 0);
+}
+
+#[test]
+fn touching_an_enemy_while_invincible_is_harmless() {
+    let mut app = start_game();
+    let cigars = app
+        .world
+        .resource::<PowerUps>()
+        .get("Cigars")
+        .unwrap()
+        .clone();
+    app.world.resource_mut::<ActivePowerUps>().activate(&cigars);
+
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+
+    assert_eq!(game_over_count(&app), 0);
+    assert_eq!(count::<Player>(&mut app), 1);
+}
+
+#[test]
+fn waves_follow_the_curve_with_an_intermission() {
+    let wave = |enemies, duration| WaveDefinition {
+        enemies,
+        reinforcement_interval: 60.0,
+        duration,
+        speed: 1.0,
+        mix: vec![(EnemyKind::Bouncer, 1)],
+        power_up_interval: 1.0,
+    };
+    let curve = WaveCurve {
+        intermission: 1.0,
+        waves: vec![wave(2, 1.0), wave(5, 1.0)],
+        endless: EndlessWaves {
+            enemies: 3,
+            speed: 0.5,
+        },
+    };
+    assert_eq!(curve.wave(4).enemies, 11);
+    assert_eq!(curve.wave(4).speed, 2.0);
+
+    let mut app = start_game_with(|app| {
+        app.insert_resource(curve);
+    });
+    assert_eq!(app.world.resource::<Wave>().number, 1);
+    assert_eq!(count::<Enemy>(&mut app), 2);
+
+    advance(&mut app, 60);
+    assert_eq!(app.world.resource::<Wave>().phase, WavePhase::Intermission);
+    assert_eq!(count::<Enemy>(&mut app), 0);
+
+    advance(&mut app, 60);
+    let wave = app.world.resource::<Wave>();
+    assert_eq!((wave.number, wave.phase), (2, WavePhase::Active));
+    assert_eq!(count::<Enemy>(&mut app), 5);
+}
+
+#[test]
+fn chasers_steer_toward_the_player_at_a_capped_rate() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+
+    let chaser = app
+        .world
+        .spawn((
+            Transform::from_xyz(600.0, 0.0, 0.0),
+            Enemy { direction: Vec2::Y },
+            EnemyKind::Chaser,
+        ))
+        .id();
+    advance(&mut app, 1);
+
+    let direction = app.world.get::<Enemy>(chaser).unwrap().direction;
+    let turned = Vec2::Y.angle_between(direction);
+    assert!(turned > 0.0 && turned <= CHASER_TURN_RATE * TIME_STEP + 1e-4);
+
+    advance(&mut app, 120);
+    let direction = app.world.get::<Enemy>(chaser).unwrap().direction;
+    let distance = app
+        .world
+        .get::<Transform>(chaser)
+        .unwrap()
+        .translation
+        .length();
+    assert!(direction.x < -0.9, "still heading {:?}", direction);
+    assert!(distance < 600.0);
+}
+
+#[test]
+fn enemies_bounce_off_each_other() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+
+    let left = app
+        .world
+        .spawn((
+            Transform::from_xyz(-30.0, 300.0, 0.0),
+            Enemy { direction: Vec2::X },
+        ))
+        .id();
+    let right = app
+        .world
+        .spawn((
+            Transform::from_xyz(30.0, 300.0, 0.0),
+            Enemy {
+                direction: Vec2::NEG_X,
+            },
+        ))
+        .id();
+    advance(&mut app, 1);
+
+    let direction = |app: &App, entity| app.world.get::<Enemy>(entity).unwrap().direction;
+    assert!(direction(&app, left).abs_diff_eq(Vec2::NEG_X, 1e-5));
+    assert!(direction(&app, right).abs_diff_eq(Vec2::X, 1e-5));
+
+    let position = |app: &App, entity| app.world.get::<Transform>(entity).unwrap().translation;
+    assert!(position(&app, left).distance(position(&app, right)) >= PLAYER_SIZE - 1e-3);
+}
+
+#[test]
+fn cigars_grant_thirty_seconds_of_invincibility() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+
+    spawn_near_player(
+        &mut app,
+        PowerUpPickup {
+            name: "Cigars".to_string(),
+        },
+    );
+    advance(&mut app, 2);
+    assert!(app.world.resource::<ActivePowerUps>().invincible());
+    assert_eq!(count::<PowerUpPickup>(&mut app), 0);
+
+    clear_enemies(&mut app);
+    advance(&mut app, 29 * 60);
+    assert!(app.world.resource::<ActivePowerUps>().invincible());
+
+    clear_enemies(&mut app);
+    advance(&mut app, 2 * 60);
+    assert!(!app.world.resource::<ActivePowerUps>().invincible());
+}
+
+#[test]
+fn coffee_spawns_the_star_bonus() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    let stars = count::<Star>(&mut app);
+
+    spawn_near_player(
+        &mut app,
+        PowerUpPickup {
+            name: "Coffee".to_string(),
+        },
+    );
+    advance(&mut app, 1);
+
+    assert_eq!(count::<PowerUpPickup>(&mut app), 0);
+    assert_eq!(count::<Star>(&mut app), stars + NUMBER_OF_STARS * 3);
+}
+
+fn run_out_power_up_timers(app: &mut App) {
+    let mut timers = app.world.resource_mut::<PowerUpTimers>();
+    for timer in timers.timers.values_mut() {
+        let duration = timer.duration();
+        timer.set_elapsed(duration);
+    }
+}
+
+#[test]
+fn every_power_up_spawns_on_its_own_timer() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    assert!(!PowerUps::default().power_ups.is_empty());
+
+    let mut names: Vec<String> = app
+        .world
+        .resource::<PowerUps>()
+        .power_ups
+        .iter()
+        .map(|power_up| power_up.name.clone())
+        .collect();
+    names.sort();
+    run_out_power_up_timers(&mut app);
+    advance(&mut app, 1);
+
+    let mut spawned: Vec<String> = app
+        .world
+        .query::<&PowerUpPickup>()
+        .iter(&app.world)
+        .map(|pickup| pickup.name.clone())
+        .collect();
+    spawned.sort();
+    assert_eq!(spawned, names);
+
+    // Nothing more turns up while they are still waiting to be collected.
+    run_out_power_up_timers(&mut app);
+    advance(&mut app, 1);
+    assert_eq!(count::<PowerUpPickup>(&mut app), names.len());
+}
+
+#[test]
+fn the_magnet_pulls_nearby_money_and_stacks_with_coffee() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    spawn_near_player(
+        &mut app,
+        PowerUpPickup {
+            name: "Magnet".to_string(),
+        },
+    );
+    advance(&mut app, 1);
+    assert!(app.world.resource::<ActivePowerUps>().is_active("Magnet"));
+
+    let player = player_translation(&mut app);
+    let near = app
+        .world
+        .spawn((
+            Transform::from_translation(player + Vec3::X * 200.0),
+            Star {},
+        ))
+        .id();
+    let far = app
+        .world
+        .spawn((
+            Transform::from_translation(player - Vec3::X * 500.0),
+            Star {},
+        ))
+        .id();
+    let score = app.world.resource::<Score>().value;
+    advance(&mut app, 60);
+
+    assert!(app.world.get_entity(near).is_none());
+    assert!(app.world.resource::<Score>().value > score);
+    assert_eq!(
+        app.world.get::<Transform>(far).unwrap().translation,
+        player - Vec3::X * 500.0
+    );
+
+    clear_enemies(&mut app);
+    spawn_near_player(
+        &mut app,
+        PowerUpPickup {
+            name: "Coffee".to_string(),
+        },
+    );
+    advance(&mut app, 1);
+    let active_power_ups = app.world.resource::<ActivePowerUps>();
+    assert!(active_power_ups.is_active("Magnet"));
+    assert!(!active_power_ups.invincible());
+
+    // The burst lands all over the arena; bring it within reach of the magnet.
+    let burst: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, With<Star>>()
+        .iter(&app.world)
+        .filter(|&star| star != far)
+        .collect();
+    assert!(!burst.is_empty());
+    let player = player_translation(&mut app);
+    for (index, &star) in burst.iter().enumerate() {
+        let angle = index as f32 / burst.len() as f32 * std::f32::consts::TAU;
+        let position = player + Vec2::from_angle(angle).extend(0.0) * 250.0;
+        app.world.entity_mut(star).insert((
+            Transform::from_translation(position),
+            Interpolated::new(position),
+        ));
+    }
+    let score = app.world.resource::<Score>().value;
+    advance(&mut app, 60);
+
+    assert!(burst
+        .iter()
+        .all(|&star| app.world.get_entity(star).is_none()));
+    assert!(app.world.resource::<Score>().value > score);
+}
+
+#[test]
+fn time_slow_slows_agents_but_not_the_player() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    spawn_near_player(
+        &mut app,
+        PowerUpPickup {
+            name: "Time Slow".to_string(),
+        },
+    );
+    advance(&mut app, 2);
+    let scale = app.world.resource::<EnemyTimeScale>().value;
+    assert!(scale < 1.0);
+
+    clear_enemies(&mut app);
+    let start = Vec3::new(300.0, 0.0, 0.0);
+    let enemy = app
+        .world
+        .spawn((
+            Transform::from_translation(start),
+            Enemy { direction: Vec2::X },
+        ))
+        .id();
+    advance(&mut app, 10);
+    let travelled = app.world.get::<Transform>(enemy).unwrap().translation.x - start.x;
+    let expected = ENEMY_SPEED * scale * 10.0 * TIME_STEP;
+    assert!(
+        (travelled - expected).abs() < 1e-2,
+        "{travelled} vs {expected}"
+    );
+
+    let before = player_translation(&mut app);
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+    advance(&mut app, 11);
+    let moved = player_translation(&mut app).x - before.x;
+    assert!(moved >= PLAYER_SPEED * 10.0 * TIME_STEP - 1e-2, "{moved}");
+}
+
+fn dash(app: &mut App) {
+    app.world
+        .resource_mut::<Input<KeyCode>>()
+        .press(KeyCode::ShiftLeft);
+    advance(app, 1);
+    app.world
+        .resource_mut::<Input<KeyCode>>()
+        .release(KeyCode::ShiftLeft);
+}
+
+#[test]
+fn dashing_bursts_ahead_through_agents_and_picks_up_money() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::D);
+    advance(&mut app, 1);
+
+    let start = player_translation(&mut app);
+    let star = app
+        .world
+        .spawn((
+            Transform::from_translation(start + Vec3::X * 150.0),
+            Star {},
+        ))
+        .id();
+    let enemy = app
+        .world
+        .spawn((
+            Transform::from_translation(start + Vec3::X * 200.0),
+            Enemy { direction: Vec2::Y },
+        ))
+        .id();
+    dash(&mut app);
+    let dash_ticks = (DASH_SECONDS / TIME_STEP).round() as usize;
+    advance(&mut app, dash_ticks);
+    app.world.despawn(enemy);
+
+    let travelled = player_translation(&mut app).x - start.x;
+    assert!(travelled >= DASH_DISTANCE, "only dashed {travelled}");
+    assert!(app.world.get_entity(star).is_none());
+    assert_eq!(player_lives(&mut app), StartingLives::default().value);
+
+    // Still cooling down, so this one is just a step.
+    let before = player_translation(&mut app);
+    dash(&mut app);
+    let step = player_translation(&mut app).x - before.x;
+    assert!(
+        step <= PLAYER_SPEED * TIME_STEP + 1e-3,
+        "dashed again: {step}"
+    );
+
+    // Dashing into the wall stops at the wall.
+    advance(&mut app, (DASH_COOLDOWN_SECONDS / TIME_STEP) as usize);
+    clear_enemies(&mut app);
+    dash(&mut app);
+    advance(&mut app, 60);
+    let arena_width = app.world.resource::<Arena>().width;
+    assert_eq!(
+        player_translation(&mut app).x,
+        arena_width / 2.0 - PLAYER_SIZE / 2.0
+    );
+}
+
+fn player_shields(app: &mut App) -> u32 {
+    app.world
+        .query_filtered::<&Shields, With<Player>>()
+        .single(&app.world)
+        .count
+}
+
+#[test]
+fn shields_stack_to_a_cap_and_each_absorbs_one_hit() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    for _ in 0..5 {
+        spawn_near_player(
+            &mut app,
+            PowerUpPickup {
+                name: "Shield".to_string(),
+            },
+        );
+        advance(&mut app, 1);
+    }
+    assert_eq!(player_shields(&mut app), 3);
+
+    clear_enemies(&mut app);
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+    assert_eq!(player_shields(&mut app), 2);
+    assert_eq!(player_lives(&mut app), StartingLives::default().value);
+    assert_eq!(count::<Enemy>(&mut app), 0);
+    assert_eq!(app.world.resource::<Events<ShieldAbsorbed>>().len(), 1);
+
+    let recovery_ticks = (SHIELD_RECOVERY_SECONDS / TIME_STEP).ceil() as usize;
+    for _ in 0..2 {
+        advance(&mut app, recovery_ticks);
+        spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+        advance(&mut app, 1);
+    }
+    assert_eq!(player_shields(&mut app), 0);
+    advance(&mut app, recovery_ticks);
+    clear_enemies(&mut app);
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 1);
+    assert_eq!(player_lives(&mut app), StartingLives::default().value - 1);
+}
+
+#[test]
+fn a_shield_hit_gives_a_moment_to_get_clear() {
+    let mut app = start_game();
+    clear_enemies(&mut app);
+    spawn_near_player(
+        &mut app,
+        PowerUpPickup {
+            name: "Shield".to_string(),
+        },
+    );
+    advance(&mut app, 1);
+    assert_eq!(player_shields(&mut app), 1);
+
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    spawn_near_player(&mut app, Enemy { direction: Vec2::X });
+    advance(&mut app, 2);
+    assert_eq!(player_shields(&mut app), 0);
+    assert_eq!(count::<Enemy>(&mut app), 1);
+    assert_eq!(player_lives(&mut app), StartingLives::default().value);
+}
+
+#[test]
+fn replays_reproduce_the_run() {
+    let mut app = start_game();
+    let keys = [KeyCode::D, KeyCode::W, KeyCode::A, KeyCode::S];
+    let mut ticks = 0;
+    while game_over_count(&app) == 0 {
+        let mut keyboard_input = app.world.resource_mut::<Input<KeyCode>>();
+        keyboard_input.release_all();
+        keyboard_input.press(keys[(ticks / 45) % keys.len()]);
+        advance(&mut app, 1);
+
+        ticks += 1;
+        assert!(ticks < 60 * 600, "the run never ended");
+    }
+    advance(&mut app, 1);
+    let replay = app
+        .world
+        .resource_mut::<ReplayRecorder>()
+        .last
+        .take()
+        .unwrap();
+    let (score, length) = (replay.score, replay.inputs.len());
+
+    let mut playback = App::new();
+    playback
+        .insert_resource(GameRng::new(Some(replay.seed)))
+        .insert_resource(ReplayPlayback { replay, cursor: 0 })
+        .add_plugins(HeadlessPlugin);
+    playback.update();
+    playback.world.send_event(GameStart {});
+    let mut played = 0;
+    while game_over_count(&playback) == 0 {
+        advance(&mut playback, 1);
+        if *playback.world.resource::<State<GameState>>().get() == GameState::Game {
+            played += 1;
+        }
+        assert!(played <= length, "the replay outlived the recording");
+    }
+
+    assert_eq!(playback.world.resource::<Score>().value, score);
+    assert_eq!(played, length);
+    assert!(!playback.world.contains_resource::<ReplayPlayback>());
+}
@@ -0,0 +1,42 @@
+use top_g_simulator::{resources::*, utils::*};
+
+fn entry(name: &str, score: u32) -> HighScore {
+    HighScore {
+        name: name.to_string(),
+        score,
+        wave: 2,
+        seed: 1234,
+        survival_secs: 75.0,
+        timestamp: 1_700_000_000,
+    }
+}
+
+#[test]
+fn the_table_keeps_the_best_runs_in_order() {
+    let mut high_scores = HighScores::default();
+    assert!(!high_scores.qualifies(0));
+
+    for score in 1..=HighScores::CAPACITY as u32 {
+        high_scores.insert(entry("filler", score));
+    }
+    assert_eq!(high_scores.entries.len(), HighScores::CAPACITY);
+    assert!(!high_scores.qualifies(1));
+
+    assert_eq!(high_scores.insert(entry("tate", 5)), Some(6));
+    assert_eq!(high_scores.insert(entry("best", 100)), Some(0));
+    assert_eq!(high_scores.insert(entry("worst", 1)), None);
+
+    assert_eq!(high_scores.entries.len(), HighScores::CAPACITY);
+    assert_eq!(high_scores.entries[0].name, "best");
+    assert_eq!(high_scores.entries[7].name, "tate");
+    let scores: Vec<u32> = high_scores.entries.iter().map(|e| e.score).collect();
+    assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+}
+
+#[test]
+fn dates_and_durations_are_formatted_for_the_table() {
+    assert_eq!(format_date(0), "1970-01-01");
+    assert_eq!(format_date(951_782_400), "2000-02-29");
+    assert_eq!(format_date(1_700_000_000), "2023-11-14");
+    assert_eq!(format_duration(75.9), "1:15");
+}
@@ -0,0 +1,32 @@
+use std::fs;
+
+use bevy::prelude::KeyCode;
+use top_g_simulator::{resources::*, utils::*};
+
+#[test]
+fn saves_survive_a_round_trip_through_disk() {
+    let path = std::env::temp_dir()
+        .join(format!("top-g-persistence-{}", std::process::id()))
+        .join(Settings::FILE);
+
+    let mut settings = Settings {
+        display_mode: DisplayMode::Windowed,
+        resolution: (1280, 720),
+        vsync: false,
+        show_fps: false,
+        screen_shake: false,
+        music: false,
+        master_volume: 0.5,
+        music_volume: 0.25,
+        sfx_volume: 0.75,
+        bindings: Bindings::default(),
+        control_mode: ControlMode::Mouse,
+    };
+    settings.bindings.rebind(Action::Pause, KeyCode::P);
+    save_ron(&settings, &path).unwrap();
+    assert_eq!(load_ron::<Settings>(&path).unwrap(), settings);
+
+    fs::write(&path, "(vsync: false").unwrap();
+    assert!(load_ron::<Settings>(&path).is_err());
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,83 @@
+use bevy::{app::Plugins, prelude::*};
+
+use top_g_simulator::{components::*, events::*, plugins::*, resources::*};
+
+/// A headless game plus `plugins`, playing back a replay with no input, with
+/// the menu state and window events those plugins expect. Saves go to a
+/// scratch data directory, so nothing on the machine is touched.
+fn play_back<M>(plugins: impl Plugins<M>) -> App {
+    std::env::set_var(
+        "XDG_DATA_HOME",
+        std::env::temp_dir().join(format!("top-g-replays-{}", std::process::id())),
+    );
+
+    let replay = Replay::new(1234, &Arena::default());
+    let mut app = App::new();
+    app.insert_resource(GameRng::new(Some(replay.seed)))
+        .insert_resource(StartingLives { value: 1 })
+        .insert_resource(ReplayPlayback { replay, cursor: 0 })
+        .add_state::<MenuScreen>()
+        .add_event::<ReceivedCharacter>()
+        .add_plugins(HeadlessPlugin)
+        .add_plugins(plugins);
+    app.update();
+
+    app.world.send_event(GameStart {});
+    app.update();
+    app.update();
+    assert_eq!(
+        *app.world.resource::<State<GameState>>().get(),
+        GameState::Game
+    );
+    app
+}
+
+/// Ends the replay on an agent with a score worth keeping, then lets a few
+/// more frames go by.
+fn finish_rich(app: &mut App) {
+    app.world.resource_mut::<Score>().value = 10_000;
+    let player = app
+        .world
+        .query_filtered::<&Transform, With<Player>>()
+        .single(&app.world)
+        .translation;
+    app.world.spawn((
+        Transform::from_translation(player),
+        Enemy { direction: Vec2::X },
+    ));
+    for _ in 0..5 {
+        app.update();
+    }
+    assert!(!app.world.contains_resource::<ReplayPlayback>());
+}
+
+#[test]
+fn replays_do_not_make_the_high_scores() {
+    let mut app = play_back(HighScorePlugin);
+    finish_rich(&mut app);
+
+    assert!(!app.world.contains_resource::<PendingHighScore>());
+    assert_eq!(
+        *app.world.resource::<State<MenuScreen>>().get(),
+        MenuScreen::Main
+    );
+}
+
+#[test]
+fn replays_do_not_count_toward_lifetime_stats() {
+    let mut app = play_back(StatsPlugin);
+    finish_rich(&mut app);
+
+    assert_eq!(
+        *app.world.resource::<LifetimeStats>(),
+        LifetimeStats::default()
+    );
+}
+
+#[test]
+fn replays_do_not_unlock_achievements() {
+    let mut app = play_back(AchievementPlugin);
+    finish_rich(&mut app);
+
+    assert!(app.world.resource::<UnlockedAchievements>().ids.is_empty());
+}
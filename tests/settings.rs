@@ -0,0 +1,60 @@
+use bevy::prelude::KeyCode;
+use top_g_simulator::{components::OptionKind, resources::*};
+
+#[test]
+fn missing_fields_keep_their_defaults() {
+    let settings: Settings = ron::from_str("(music: false, sfx_volume: 0.3)").unwrap();
+    assert!(!settings.music);
+    assert_eq!(settings.sfx_volume, 0.3);
+    assert_eq!(settings.resolution, Settings::default().resolution);
+    assert_eq!(settings.display_mode, Settings::default().display_mode);
+}
+
+#[test]
+fn options_step_volumes_and_flip_toggles() {
+    let mut settings = Settings::default();
+
+    OptionKind::MusicVolume.adjust(&mut settings, -1);
+    OptionKind::MusicVolume.adjust(&mut settings, -1);
+    assert_eq!(settings.music_volume, 0.8);
+    assert_eq!(
+        OptionKind::MusicVolume.label(&settings),
+        "Music Volume: 80%"
+    );
+
+    OptionKind::SfxVolume.adjust(&mut settings, 1);
+    assert_eq!(settings.sfx_volume, 1.0);
+    for _ in 0..15 {
+        OptionKind::MasterVolume.adjust(&mut settings, -1);
+    }
+    assert_eq!(settings.master_volume, 0.0);
+
+    OptionKind::Fullscreen.adjust(&mut settings, 1);
+    assert_eq!(settings.display_mode, DisplayMode::Windowed);
+    OptionKind::Fullscreen.adjust(&mut settings, -1);
+    assert_eq!(settings.display_mode, DisplayMode::Fullscreen);
+
+    OptionKind::ScreenShake.adjust(&mut settings, 1);
+    assert!(!settings.screen_shake);
+    assert_eq!(
+        OptionKind::ScreenShake.label(&settings),
+        "Screen Shake: Off"
+    );
+}
+
+#[test]
+fn rebinding_a_key_takes_it_from_other_actions() {
+    let mut bindings = Bindings::default();
+    assert!(bindings.keys(Action::MoveUp).contains(&KeyCode::W));
+
+    bindings.rebind(Action::Pause, KeyCode::W);
+    assert_eq!(bindings.keys(Action::Pause), vec![KeyCode::W]);
+    assert_eq!(bindings.keys(Action::MoveUp), vec![KeyCode::Up]);
+    assert_eq!(
+        OptionKind::Binding(Action::MoveUp).label(&Settings {
+            bindings,
+            ..Default::default()
+        }),
+        "Move Up: Up"
+    );
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use top_g_simulator::{events::GameOver, resources::*};
+
+fn coffee() -> BTreeMap<String, u32> {
+    BTreeMap::from([("Coffee".to_string(), 1)])
+}
+
+fn run(score: u32, wave: u32, survival_secs: f32) -> GameOver {
+    GameOver {
+        score,
+        wave,
+        survival_secs,
+        agents: 4,
+        money: score,
+        power_ups: coffee(),
+        seed: 1234,
+    }
+}
+
+fn stats(distance: f32, near_misses: u32) -> RunStats {
+    RunStats {
+        money: 0,
+        power_ups: coffee(),
+        hits: 3,
+        near_misses,
+        distance,
+        invincible_secs: 0.0,
+    }
+}
+
+#[test]
+fn runs_add_up_to_totals_averages_and_bests() {
+    let mut lifetime = LifetimeStats::default();
+    lifetime.record(&run(10, 2, 60.0), &stats(1000.0, 5));
+    lifetime.record(&run(30, 1, 30.0), &stats(3000.0, 2));
+
+    assert_eq!(lifetime.runs, 2);
+    assert_eq!(lifetime.total_score, 40);
+    assert_eq!(lifetime.hits, 6);
+    assert_eq!(lifetime.power_ups["Coffee"], 2);
+    assert_eq!(lifetime.near_misses, 7);
+    assert_eq!(lifetime.distance, 4000.0);
+    assert_eq!(lifetime.average_score(), 20.0);
+    assert_eq!(lifetime.average_survival_secs(), 45.0);
+
+    assert_eq!(lifetime.best_score, 30);
+    assert_eq!(lifetime.best_wave, 2);
+    assert_eq!(lifetime.longest_survival_secs, 60.0);
+    assert_eq!(lifetime.longest_distance, 3000.0);
+    assert_eq!(lifetime.most_near_misses, 5);
+}
+
+#[test]
+fn fields_missing_from_the_file_start_from_zero() {
+    let partial: LifetimeStats = ron::from_str("(runs: 7, best_score: 40)").unwrap();
+    assert_eq!(partial.runs, 7);
+    assert_eq!(partial.best_score, 40);
+    assert_eq!(partial.total_score, 0);
+    assert_eq!(partial.most_near_misses, 0);
+}